@@ -25,6 +25,18 @@ impl graphics::Grid for Grid<'_> {
     fn right_click_cell(&mut self, coord: &Coord) {
         self.local_player_listener.on_right_click(coord)
     }
+
+    fn chord_cell(&mut self, coord: &Coord) {
+        self.local_player_listener.on_chord(coord)
+    }
+
+    fn undo(&mut self) {
+        self.local_player_listener.on_undo()
+    }
+
+    fn chat(&mut self, text: &str) {
+        self.local_player_listener.on_chat(text)
+    }
 }
 
 pub struct Main<M>
@@ -80,50 +92,86 @@ where
         }
         match &mut self.status {
             Status::Running(session) => session
-                .snapshot(|snapshot| -> Result<R, (F, graphics::Statistics, bool)> {
-                    let game::session::SessionSnapshot {
-                        stats:
-                            game::Stats {
-                                lives_left,
-                                mines_left,
-                            },
-                        user_stats,
-                        status,
-                        coords,
-                        namer,
-                        field_provider,
-                        local_player_listener,
-                    } = snapshot;
-                    if let game::Status::Ended { success } = status {
-                        let stats = to_graphics_stats(user_stats, namer);
-                        return Err((f, stats, success));
-                    }
-                    let lives_left = graphics::LivesLeft(lives_left);
-                    let mines_left = graphics::MinesLeft(mines_left);
-                    let grid = Grid {
-                        local_player_listener,
-                        field_provider,
-                        namer,
-                    };
-                    Ok(f(graphics::Status::Active(graphics::Active {
-                        coords,
-                        grid,
-                        lives_left,
-                        mines_left,
-                    })))
-                })
-                .unwrap_or_else(|(f, stats, success)| {
-                    self.status = Status::Done { stats, success };
+                .snapshot(
+                    |snapshot| -> Result<R, (F, Box<graphics::Statistics>, graphics::Outcome)> {
+                        let game::session::SessionSnapshot {
+                            stats:
+                                game::Stats {
+                                    lives_left,
+                                    mines_left,
+                                    clock,
+                                    three_bv,
+                                },
+                            user_stats,
+                            status,
+                            coords,
+                            players,
+                            spectators,
+                            teams,
+                            chat,
+                            connection,
+                            namer,
+                            field_provider,
+                            local_player_listener,
+                        } = snapshot;
+                        let time = graphics::Time(clock.elapsed());
+                        let outcome = match (connection, status) {
+                            (game::Connection::Lost, _) => Some(graphics::Outcome::Disconnected),
+                            (_, game::Status::Ended { success: true }) => {
+                                Some(graphics::Outcome::Won)
+                            }
+                            (_, game::Status::Ended { success: false }) => {
+                                Some(graphics::Outcome::Failed)
+                            }
+                            (_, game::Status::Playing) => None,
+                        };
+                        if let Some(outcome) = outcome {
+                            let teams = teams
+                                .map(|teams| to_graphics_teams(&teams, namer))
+                                .unwrap_or_default();
+                            let board = three_bv.map(|three_bv| {
+                                to_graphics_board(three_bv, &user_stats, clock.elapsed())
+                            });
+                            let stats = to_graphics_stats(user_stats, namer, time, teams, board);
+                            return Err((f, Box::new(stats), outcome));
+                        }
+                        let rivals = teams
+                            .map(|teams| to_graphics_rivals(&teams, namer))
+                            .unwrap_or_default();
+                        let players = to_graphics_players(&players, &user_stats, namer);
+                        let chat = chat.map(|chat| to_graphics_chat(&chat, namer));
+                        let lives_left = graphics::LivesLeft(lives_left);
+                        let mines_left = graphics::MinesLeft(mines_left);
+                        let grid = Grid {
+                            local_player_listener,
+                            field_provider,
+                            namer,
+                        };
+                        Ok(f(graphics::Status::Active(graphics::Active {
+                            coords,
+                            grid,
+                            lives_left,
+                            mines_left,
+                            time,
+                            players,
+                            spectators,
+                            rivals,
+                            chat,
+                        })))
+                    },
+                )
+                .unwrap_or_else(|(f, stats, outcome)| {
+                    self.status = Status::Done { stats, outcome };
                     self.requested_status = RequestedStatus::Done;
                     self.status(f)
                 }),
-            Status::Done { stats, success } => {
+            Status::Done { stats, outcome } => {
                 let nonactive = graphics::NonActive {
                     controller: RunningStatusRequester::new(&mut self.requested_status),
                     stats,
                 };
-                let success = *success;
-                f(graphics::Status::NonActive { nonactive, success })
+                let outcome = *outcome;
+                f(graphics::Status::NonActive { nonactive, outcome })
             }
         }
     }
@@ -151,8 +199,8 @@ where
 {
     Running(S),
     Done {
-        stats: graphics::Statistics,
-        success: bool,
+        stats: Box<graphics::Statistics>,
+        outcome: graphics::Outcome,
     },
 }
 
@@ -182,8 +230,8 @@ where
         game::Cell::HintBy(game::Hint(6), _uid) => graphics::Cell::EmptySix,
         game::Cell::HintBy(game::Hint(7), _uid) => graphics::Cell::EmptySeven,
         game::Cell::HintBy(game::Hint(8), _uid) => graphics::Cell::EmptyEight,
-        game::Cell::HintBy(game::Hint(_), _uid) => {
-            panic!("A cell on a square grid can not be surrounded by 9 mines or more")
+        game::Cell::HintBy(game::Hint(hint), _uid) => {
+            panic!("A cell can not be surrounded by {hint} mines, the maximum is {MAX_SURROUNDING_MINES}")
         }
         game::Cell::QuestionMarked(uid) => graphics::Cell::Maybe(to_player(uid)),
         game::Cell::FlaggedBy(uid) => graphics::Cell::Flag(to_player(uid)),
@@ -198,7 +246,7 @@ fn to_graphics_stat<S>(
     number: S,
 ) -> Vec<graphics::UserStat>
 where
-    S: Fn(&game::UserStat) -> u32,
+    S: Fn(&game::UserStat) -> i64,
 {
     let game::UserStats(stats) = stats;
     let mut stats: Vec<graphics::UserStat> = stats
@@ -213,17 +261,156 @@ where
     stats
 }
 
-fn to_graphics_stats(stats: game::UserStats, namer: &dyn Namer) -> graphics::Statistics {
-    let marked_correct = to_graphics_stat(&stats, namer, |stat| stat.marked_correct);
-    let marked_incorrect = to_graphics_stat(&stats, namer, |stat| stat.marked_incorrect);
-    let exploded = to_graphics_stat(&stats, namer, |stat| stat.exploded);
+fn to_graphics_uncovered(stats: &game::UserStats, namer: &dyn Namer) -> Vec<graphics::UncoverStat> {
+    let game::UserStats(stats) = stats;
+    let mut uncovered: Vec<graphics::UncoverStat> = stats
+        .iter()
+        .map(|(&uid, stat)| graphics::UncoverStat {
+            id: uid.into(),
+            name: namer.name(uid).into(),
+            revealed: stat.revealed,
+            hints: stat.hints,
+            openings: stat.openings,
+            biggest_opening: stat.biggest_opening,
+        })
+        .collect();
+    uncovered.sort_by_key(|stat| std::cmp::Reverse(stat.revealed));
+    uncovered
+}
+
+fn to_graphics_clicks(stats: &game::UserStats, namer: &dyn Namer) -> Vec<graphics::ClickStat> {
+    let game::UserStats(stats) = stats;
+    let mut clicks: Vec<graphics::ClickStat> = stats
+        .iter()
+        .map(|(&uid, stat)| graphics::ClickStat {
+            id: uid.into(),
+            name: namer.name(uid).into(),
+            effective: stat.effective_clicks,
+            wasted: stat.wasted_clicks,
+        })
+        .collect();
+    clicks.sort_by_key(|stat| std::cmp::Reverse(stat.effective));
+    clicks
+}
+
+fn to_graphics_board(
+    three_bv: u32,
+    stats: &game::UserStats,
+    elapsed: std::time::Duration,
+) -> graphics::Board {
+    let game::UserStats(stats) = stats;
+    let clicks: u32 = stats
+        .values()
+        .map(|stat| stat.effective_clicks + stat.wasted_clicks)
+        .sum();
+    let seconds = elapsed.as_secs_f64();
+    graphics::Board {
+        three_bv,
+        three_bv_per_second: (seconds > 0.).then(|| f64::from(three_bv) / seconds),
+        efficiency: (clicks > 0).then(|| f64::from(three_bv) / f64::from(clicks)),
+    }
+}
+
+fn to_graphics_stats(
+    stats: game::UserStats,
+    namer: &dyn Namer,
+    time: graphics::Time,
+    teams: Vec<graphics::TeamProgress>,
+    board: Option<graphics::Board>,
+) -> graphics::Statistics {
+    let scores = to_graphics_stat(&stats, namer, |stat| i64::from(stat.score));
+    let marked_correct = to_graphics_stat(&stats, namer, |stat| i64::from(stat.marked_correct));
+    let marked_incorrect = to_graphics_stat(&stats, namer, |stat| i64::from(stat.marked_incorrect));
+    let exploded = to_graphics_stat(&stats, namer, |stat| i64::from(stat.exploded));
+    let uncovered = to_graphics_uncovered(&stats, namer);
+    let clicks = to_graphics_clicks(&stats, namer);
     graphics::Statistics {
+        uncovered,
+        clicks,
+        board,
+        scores,
         exploded,
         marked_correct,
         marked_incorrect,
+        time,
+        teams,
     }
 }
 
+fn to_graphics_team(
+    progress: &game::TeamProgress,
+    winner: Option<game::Team>,
+    namer: &dyn Namer,
+) -> graphics::TeamProgress {
+    let game::TeamProgress {
+        team,
+        players,
+        uncovered,
+        safe,
+        lives_left,
+    } = progress;
+    graphics::TeamProgress {
+        name: team.to_string(),
+        players: players.iter().map(|&uid| namer.name(uid).into()).collect(),
+        uncovered: *uncovered,
+        safe: *safe,
+        lives_left: *lives_left,
+        winner: winner == Some(*team),
+    }
+}
+
+fn to_graphics_teams(
+    standings: &game::Standings,
+    namer: &dyn Namer,
+) -> Vec<graphics::TeamProgress> {
+    standings
+        .teams
+        .iter()
+        .map(|progress| to_graphics_team(progress, standings.winner, namer))
+        .collect()
+}
+
+/// Every team but the one of which the minefield is shown.
+fn to_graphics_rivals(
+    standings: &game::Standings,
+    namer: &dyn Namer,
+) -> Vec<graphics::TeamProgress> {
+    standings
+        .teams
+        .iter()
+        .filter(|progress| progress.team != standings.shown)
+        .map(|progress| to_graphics_team(progress, standings.winner, namer))
+        .collect()
+}
+
+fn to_graphics_players(
+    players: &[(game::SessionUserID, game::Connection)],
+    stats: &game::UserStats,
+    namer: &dyn Namer,
+) -> Vec<graphics::PlayerState> {
+    let game::UserStats(stats) = stats;
+    players
+        .iter()
+        .map(|&(uid, connection)| graphics::PlayerState {
+            id: uid.into(),
+            name: namer.name(uid).into(),
+            connected: connection == game::Connection::Connected,
+            lives_left: stats.get(&uid).and_then(|stat| stat.lives_left),
+            score: stats.get(&uid).map_or(0, |stat| stat.score),
+        })
+        .collect()
+}
+
+fn to_graphics_chat(chat: &game::chat::ChatHistory, namer: &dyn Namer) -> Vec<graphics::ChatLine> {
+    chat.lines()
+        .map(|game::chat::ChatLine { uid, text }| graphics::ChatLine {
+            id: (*uid).into(),
+            name: namer.name(*uid).into(),
+            text: text.clone(),
+        })
+        .collect()
+}
+
 impl From<graphics::PlayerID> for game::SessionUserID {
     fn from(id: graphics::PlayerID) -> Self {
         let graphics::PlayerID(id) = id;
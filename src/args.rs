@@ -3,6 +3,7 @@ use clap::{Arg, ArgAction, ArgMatches, Command};
 static SUBCMD_LOCAL: &str = "local";
 static SUBCMD_MASTER: &str = "master";
 static SUBCMD_SLAVE: &str = "slave";
+static SUBCMD_SERVE: &str = "serve";
 
 static ARG_WIDTH: &str = "width";
 static ARG_WIDTH_DEFAULT_STR: &str = "8";
@@ -12,6 +13,17 @@ static ARG_MINES: &str = "mines";
 static ARG_MINES_DEFAULT_STR: &str = "14";
 static ARG_LIVES: &str = "lives";
 static ARG_LIVES_DEFAULT_STR: &str = "3";
+static ARG_LIVES_POLICY: &str = "livespolicy";
+static ARG_LIVES_POLICY_DEFAULT_STR: &str = "shared";
+static ARG_SEED: &str = "seed";
+static ARG_FIRST_CLICK: &str = "firstclick";
+static ARG_FIRST_CLICK_DEFAULT_STR: &str = "unprotected";
+static ARG_NO_GUESS: &str = "noguess";
+static ARG_LAYOUT: &str = "layout";
+static ARG_TOPOLOGY: &str = "topology";
+static ARG_TOPOLOGY_DEFAULT_STR: &str = "rectangular";
+static ARG_MASK: &str = "mask";
+static ARG_UNDO: &str = "undo";
 
 static ARG_MASTERIP: &str = "masterip";
 static ARG_MASTERPORT: &str = "masterport";
@@ -19,6 +31,16 @@ static ARG_SLAVEIP: &str = "slaveip";
 static ARG_SLAVEPORT: &str = "slaveport";
 
 static ARG_NAME: &str = "name";
+static ARG_TRANSPORT: &str = "transport";
+static ARG_TRANSPORT_DEFAULT_STR: &str = "udp";
+static ARG_CODEC: &str = "codec";
+static ARG_CODEC_DEFAULT_STR: &str = "binary";
+static ARG_TIMEOUT: &str = "timeout";
+static ARG_TIMEOUT_DEFAULT_STR: &str = "5";
+static ARG_REJOIN: &str = "rejoin";
+static ARG_SPECTATE: &str = "spectate";
+static ARG_TEAMS: &str = "teams";
+static ARG_TEAMS_DEFAULT_STR: &str = "1";
 
 #[derive(Debug)]
 pub struct Config {
@@ -26,6 +48,57 @@ pub struct Config {
     pub height: u32,
     pub mines: u32,
     pub lives: u32,
+    pub lives_policy: LivesPolicy,
+    pub seed: Option<u64>,
+    pub first_click: FirstClick,
+    pub no_guess: bool,
+    pub layout: Option<std::path::PathBuf>,
+    pub topology: Topology,
+    pub mask: Option<std::path::PathBuf>,
+    /// Always enabled in a singleplayer game.
+    pub undo: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum LivesPolicy {
+    Shared,
+    PerPlayer,
+    Unlimited,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum FirstClick {
+    Unprotected,
+    Safe,
+    Opening,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Topology {
+    Rectangular,
+    Hexagonal,
+    Toroidal,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Codec {
+    Binary,
+    Json,
+}
+
+impl std::fmt::Display for Transport {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transport::Udp => write!(fmt, "UDP"),
+            Transport::Tcp => write!(fmt, "TCP"),
+        }
+    }
 }
 
 impl std::fmt::Display for Config {
@@ -35,12 +108,56 @@ impl std::fmt::Display for Config {
             height,
             mines,
             lives,
+            lives_policy,
+            seed,
+            first_click,
+            no_guess,
+            layout,
+            topology,
+            mask,
+            undo,
         } = self;
         writeln!(fmt, "Minefield: width = {width}, height = {height}.")?;
-        write!(
-            fmt,
-            "There are {mines} mines and players have a combined total of {lives} lives."
-        )
+        match lives_policy {
+            LivesPolicy::Shared => write!(
+                fmt,
+                "There are {mines} mines and players have a combined total of {lives} lives."
+            )?,
+            LivesPolicy::PerPlayer => write!(
+                fmt,
+                "There are {mines} mines and every player has {lives} lives."
+            )?,
+            LivesPolicy::Unlimited => write!(
+                fmt,
+                "There are {mines} mines and lives are unlimited, but every explosion counts against the player."
+            )?,
+        }
+        if let Some(seed) = seed {
+            write!(fmt, "\nThe minefield is generated from seed {seed}.")?;
+        }
+        match first_click {
+            FirstClick::Unprotected => {}
+            FirstClick::Safe => write!(fmt, "\nThe first click never hits a mine.")?,
+            FirstClick::Opening => write!(fmt, "\nThe first click always opens an area.")?,
+        }
+        if *no_guess {
+            write!(fmt, "\nThe minefield can be cleared without guessing.")?;
+        }
+        if let Some(layout) = layout {
+            write!(fmt, "\nThe mines are laid out as in {}.", layout.display())?;
+        }
+        match topology {
+            Topology::Rectangular => {}
+            Topology::Hexagonal => write!(fmt, "\nThe cells are hexagonal.")?,
+            Topology::Toroidal => write!(fmt, "\nThe edges of the minefield wrap around.")?,
+        }
+        if let Some(mask) = mask {
+            write!(fmt, "\nThe minefield is shaped as in {}.", mask.display())?;
+        }
+        if *undo {
+            write!(fmt, "\nMoves can be undone.")?;
+        }
+        Ok(())
     }
 }
 
@@ -50,11 +167,35 @@ pub enum Modus {
         name: String,
         slave: std::net::SocketAddr,
         master: std::net::SocketAddr,
+        transport: Transport,
+        codec: Codec,
+        /// How long the master may stay silent before it is considered lost.
+        timeout: std::time::Duration,
+        /// The token which the master handed out when joining before.
+        rejoin: Option<u64>,
+        /// Watch the game without playing.
+        spectate: bool,
     },
     Master {
         name: String,
         port: u16,
+        transport: Transport,
+        codec: Codec,
+        /// How long a slave may stay silent before it is considered lost.
+        timeout: std::time::Duration,
+        config: Config,
+        /// The number of teams which race each other, every team on a minefield of its own.
+        teams: u8,
+    },
+    /// A master without a window or a player of its own.
+    Serve {
+        port: u16,
+        transport: Transport,
+        codec: Codec,
+        /// How long a slave may stay silent before it is considered lost.
+        timeout: std::time::Duration,
         config: Config,
+        teams: u8,
     },
 }
 
@@ -68,19 +209,58 @@ impl std::fmt::Display for Modus {
                 name,
                 slave,
                 master,
+                transport,
+                codec: _,
+                timeout: _,
+                rejoin: _,
+                spectate,
             } => {
+                let role = match spectate {
+                    true => "spectating",
+                    false => "connecting",
+                };
                 writeln!(
                     fmt,
-                    "Multiplayer game:\nYou are {name}, connecting from {slave} to {master}"
+                    "Multiplayer game:\nYou are {name}, {role} from {slave} to {master} over {transport}"
                 )
             }
-            Self::Master { name, port, config } => {
-                write!(fmt, "Multiplayer game:\nYou are {name}, listening at port {port}. Config:\n{config}")
+            Self::Master {
+                name,
+                port,
+                transport,
+                codec: _,
+                timeout: _,
+                config,
+                teams,
+            } => {
+                write!(fmt, "Multiplayer game:\nYou are {name}, listening at port {port} over {transport}. Config:\n{config}")?;
+                write_teams(fmt, *teams)
+            }
+            Self::Serve {
+                port,
+                transport,
+                codec: _,
+                timeout: _,
+                config,
+                teams,
+            } => {
+                write!(fmt, "Headless multiplayer game:\nListening at port {port} over {transport}. Config:\n{config}")?;
+                write_teams(fmt, *teams)
             }
         }
     }
 }
 
+fn write_teams(fmt: &mut std::fmt::Formatter<'_>, teams: u8) -> std::fmt::Result {
+    if teams > 1 {
+        write!(
+            fmt,
+            "\n{teams} teams race each other on the same minefield."
+        )?;
+    }
+    Ok(())
+}
+
 fn build_app() -> ArgMatches {
     let width = Arg::new(ARG_WIDTH)
         .long("width")
@@ -106,6 +286,43 @@ fn build_app() -> ArgMatches {
         .default_value(ARG_LIVES_DEFAULT_STR)
         .value_parser(parse_u32)
         .action(ArgAction::Set);
+    let lives_policy = Arg::new(ARG_LIVES_POLICY)
+        .long("lives-policy")
+        .value_name("POLICY")
+        .default_value(ARG_LIVES_POLICY_DEFAULT_STR)
+        .value_parser(parse_lives_policy)
+        .action(ArgAction::Set);
+    let seed = Arg::new(ARG_SEED)
+        .long("seed")
+        .value_name("SEED")
+        .value_parser(parse_u64)
+        .action(ArgAction::Set);
+    let first_click = Arg::new(ARG_FIRST_CLICK)
+        .long("first-click")
+        .value_name("PROTECTION")
+        .default_value(ARG_FIRST_CLICK_DEFAULT_STR)
+        .value_parser(parse_first_click)
+        .action(ArgAction::Set);
+    let no_guess = Arg::new(ARG_NO_GUESS)
+        .long("no-guess")
+        .action(ArgAction::SetTrue);
+    let layout = Arg::new(ARG_LAYOUT)
+        .long("layout")
+        .value_name("FILE")
+        .value_parser(clap::value_parser!(std::path::PathBuf))
+        .action(ArgAction::Set);
+    let topology = Arg::new(ARG_TOPOLOGY)
+        .long("topology")
+        .value_name("TOPOLOGY")
+        .default_value(ARG_TOPOLOGY_DEFAULT_STR)
+        .value_parser(parse_topology)
+        .action(ArgAction::Set);
+    let mask = Arg::new(ARG_MASK)
+        .long("mask")
+        .value_name("FILE")
+        .value_parser(clap::value_parser!(std::path::PathBuf))
+        .action(ArgAction::Set);
+    let undo = Arg::new(ARG_UNDO).long("undo").action(ArgAction::SetTrue);
     let masterip = Arg::new(ARG_MASTERIP)
         .long("masterip")
         .value_name("MASTERIP")
@@ -134,28 +351,103 @@ fn build_app() -> ArgMatches {
         .long("name")
         .value_name("NAME")
         .required(true);
+    let transport = Arg::new(ARG_TRANSPORT)
+        .long("transport")
+        .value_name("TRANSPORT")
+        .default_value(ARG_TRANSPORT_DEFAULT_STR)
+        .value_parser(parse_transport)
+        .action(ArgAction::Set);
+    let codec = Arg::new(ARG_CODEC)
+        .long("codec")
+        .value_name("CODEC")
+        .default_value(ARG_CODEC_DEFAULT_STR)
+        .value_parser(parse_codec)
+        .action(ArgAction::Set);
+    let timeout = Arg::new(ARG_TIMEOUT)
+        .long("timeout")
+        .value_name("SECONDS")
+        .default_value(ARG_TIMEOUT_DEFAULT_STR)
+        .value_parser(parse_timeout)
+        .action(ArgAction::Set);
+    let rejoin = Arg::new(ARG_REJOIN)
+        .long("rejoin")
+        .value_name("TOKEN")
+        .value_parser(parse_u64)
+        .action(ArgAction::Set);
+    let spectate = Arg::new(ARG_SPECTATE)
+        .long("spectate")
+        .conflicts_with(ARG_REJOIN)
+        .action(ArgAction::SetTrue);
+    let teams = Arg::new(ARG_TEAMS)
+        .long("teams")
+        .value_name("TEAMS")
+        .default_value(ARG_TEAMS_DEFAULT_STR)
+        .value_parser(parse_teams)
+        .action(ArgAction::Set);
 
     let local = Command::new(SUBCMD_LOCAL)
         .about("Run a singleplayer game")
         .arg(width.clone())
         .arg(height.clone())
         .arg(mines.clone())
-        .arg(lives.clone());
+        .arg(lives.clone())
+        .arg(lives_policy.clone())
+        .arg(seed.clone())
+        .arg(first_click.clone())
+        .arg(no_guess.clone())
+        .arg(layout.clone())
+        .arg(topology.clone())
+        .arg(mask.clone());
     let slave = Command::new(SUBCMD_SLAVE)
         .about("Setup a multiplayer game server")
         .arg(name.clone())
         .arg(slaveip)
         .arg(slaveport)
         .arg(masterip)
-        .arg(masterport.clone());
+        .arg(masterport.clone())
+        .arg(transport.clone())
+        .arg(codec.clone())
+        .arg(timeout.clone())
+        .arg(rejoin)
+        .arg(spectate);
+    let serve = Command::new(SUBCMD_SERVE)
+        .about("Host a multiplayer game without playing, round after round")
+        .arg(masterport.clone())
+        .arg(transport.clone())
+        .arg(codec.clone())
+        .arg(timeout.clone())
+        .arg(teams.clone())
+        .arg(width.clone())
+        .arg(height.clone())
+        .arg(mines.clone())
+        .arg(lives.clone())
+        .arg(lives_policy.clone())
+        .arg(seed.clone())
+        .arg(first_click.clone())
+        .arg(no_guess.clone())
+        .arg(layout.clone())
+        .arg(topology.clone())
+        .arg(mask.clone());
     let master = Command::new(SUBCMD_MASTER)
         .about("Join a multiplayer game")
         .arg(name)
         .arg(masterport)
+        .arg(transport)
+        .arg(codec)
+        .arg(timeout)
+        .arg(teams)
         .arg(width)
         .arg(height)
         .arg(mines)
-        .arg(lives);
+        .arg(lives)
+        .arg(lives_policy)
+        .arg(seed)
+        .arg(first_click)
+        .arg(no_guess)
+        .arg(layout)
+        .arg(topology)
+        .arg(mask)
+        .arg(undo);
 
     Command::new("sioux-rust-minesweeper")
         .version("1.0")
@@ -163,6 +455,7 @@ fn build_app() -> ArgMatches {
         .subcommand(local)
         .subcommand(master)
         .subcommand(slave)
+        .subcommand(serve)
         .get_matches()
 }
 
@@ -171,7 +464,15 @@ fn parse_config(m: &ArgMatches) -> Config {
         width: *m.get_one(ARG_WIDTH).unwrap(),
         height: *m.get_one(ARG_WIDTH).unwrap(),
         lives: *m.get_one(ARG_LIVES).unwrap(),
+        lives_policy: *m.get_one(ARG_LIVES_POLICY).unwrap(),
         mines: *m.get_one(ARG_MINES).unwrap(),
+        seed: m.get_one(ARG_SEED).copied(),
+        first_click: *m.get_one(ARG_FIRST_CLICK).unwrap(),
+        no_guess: m.get_flag(ARG_NO_GUESS),
+        layout: m.get_one(ARG_LAYOUT).cloned(),
+        topology: *m.get_one(ARG_TOPOLOGY).unwrap(),
+        mask: m.get_one(ARG_MASK).cloned(),
+        undo: false,
     }
 }
 
@@ -186,7 +487,10 @@ fn parse_port(m: &ArgMatches, arg: &str) -> u16 {
 pub fn parse_args() -> Modus {
     let matches = build_app();
     if let Some(m) = matches.subcommand_matches(SUBCMD_LOCAL) {
-        let config = parse_config(m);
+        let config = Config {
+            undo: true,
+            ..parse_config(m)
+        };
         return Modus::Local(config);
     }
     if let Some(m) = matches.subcommand_matches(SUBCMD_SLAVE) {
@@ -201,23 +505,72 @@ pub fn parse_args() -> Modus {
             let port = parse_port(m, ARG_MASTERPORT);
             std::net::SocketAddr::new(ip, port)
         };
+        let transport = *m.get_one(ARG_TRANSPORT).unwrap();
+        let codec = *m.get_one(ARG_CODEC).unwrap();
+        let timeout = *m.get_one(ARG_TIMEOUT).unwrap();
+        let rejoin = m.get_one(ARG_REJOIN).copied();
+        let spectate = m.get_flag(ARG_SPECTATE);
         return Modus::Slave {
             name,
             slave,
             master,
+            transport,
+            codec,
+            timeout,
+            rejoin,
+            spectate,
         };
     }
     if let Some(m) = matches.subcommand_matches(SUBCMD_MASTER) {
         let name = parse_name(m);
+        let config = Config {
+            undo: m.get_flag(ARG_UNDO),
+            ..parse_config(m)
+        };
+        let port = parse_port(m, ARG_MASTERPORT);
+        let transport = *m.get_one(ARG_TRANSPORT).unwrap();
+        let codec = *m.get_one(ARG_CODEC).unwrap();
+        let timeout = *m.get_one(ARG_TIMEOUT).unwrap();
+        let teams = *m.get_one(ARG_TEAMS).unwrap();
+        return Modus::Master {
+            name,
+            config,
+            port,
+            transport,
+            codec,
+            timeout,
+            teams,
+        };
+    }
+    if let Some(m) = matches.subcommand_matches(SUBCMD_SERVE) {
         let config = parse_config(m);
         let port = parse_port(m, ARG_MASTERPORT);
-        return Modus::Master { name, config, port };
+        let transport = *m.get_one(ARG_TRANSPORT).unwrap();
+        let codec = *m.get_one(ARG_CODEC).unwrap();
+        let timeout = *m.get_one(ARG_TIMEOUT).unwrap();
+        let teams = *m.get_one(ARG_TEAMS).unwrap();
+        return Modus::Serve {
+            config,
+            port,
+            transport,
+            codec,
+            timeout,
+            teams,
+        };
     }
     let config = Config {
         width: parse_u32(ARG_WIDTH_DEFAULT_STR).unwrap(),
         height: parse_u32(ARG_HEIGHT_DEFAULT_STR).unwrap(),
         mines: parse_u32(ARG_MINES_DEFAULT_STR).unwrap(),
         lives: parse_u32(ARG_LIVES_DEFAULT_STR).unwrap(),
+        lives_policy: parse_lives_policy(ARG_LIVES_POLICY_DEFAULT_STR).unwrap(),
+        seed: None,
+        first_click: parse_first_click(ARG_FIRST_CLICK_DEFAULT_STR).unwrap(),
+        no_guess: false,
+        layout: None,
+        topology: parse_topology(ARG_TOPOLOGY_DEFAULT_STR).unwrap(),
+        mask: None,
+        undo: true,
     };
     Modus::Local(config)
 }
@@ -230,6 +583,67 @@ fn parse_u32(arg: &str) -> Result<u32, &'static str> {
     arg.parse().map_err(|_| "Invalid u32")
 }
 
+fn parse_u64(arg: &str) -> Result<u64, &'static str> {
+    arg.parse().map_err(|_| "Invalid u64")
+}
+
+fn parse_lives_policy(arg: &str) -> Result<LivesPolicy, &'static str> {
+    match arg {
+        "shared" => Ok(LivesPolicy::Shared),
+        "perplayer" => Ok(LivesPolicy::PerPlayer),
+        "unlimited" => Ok(LivesPolicy::Unlimited),
+        _ => Err("Invalid lives policy, expected shared, perplayer or unlimited"),
+    }
+}
+
+fn parse_first_click(arg: &str) -> Result<FirstClick, &'static str> {
+    match arg {
+        "unprotected" => Ok(FirstClick::Unprotected),
+        "safe" => Ok(FirstClick::Safe),
+        "opening" => Ok(FirstClick::Opening),
+        _ => Err("Invalid first click protection, expected unprotected, safe or opening"),
+    }
+}
+
+fn parse_topology(arg: &str) -> Result<Topology, &'static str> {
+    match arg {
+        "rectangular" => Ok(Topology::Rectangular),
+        "hexagonal" => Ok(Topology::Hexagonal),
+        "toroidal" => Ok(Topology::Toroidal),
+        _ => Err("Invalid topology, expected rectangular, hexagonal or toroidal"),
+    }
+}
+
+fn parse_transport(arg: &str) -> Result<Transport, &'static str> {
+    match arg {
+        "udp" => Ok(Transport::Udp),
+        "tcp" => Ok(Transport::Tcp),
+        _ => Err("Invalid transport, expected udp or tcp"),
+    }
+}
+
+fn parse_codec(arg: &str) -> Result<Codec, &'static str> {
+    match arg {
+        "binary" => Ok(Codec::Binary),
+        "json" => Ok(Codec::Json),
+        _ => Err("Invalid codec, expected binary or json"),
+    }
+}
+
+fn parse_timeout(arg: &str) -> Result<std::time::Duration, &'static str> {
+    match arg.parse() {
+        Ok(seconds) if seconds > 0 => Ok(std::time::Duration::from_secs(seconds)),
+        _ => Err("Invalid timeout, expected a positive number of seconds"),
+    }
+}
+
+fn parse_teams(arg: &str) -> Result<u8, &'static str> {
+    match arg.parse() {
+        Ok(teams) if (1..=crate::MAX_PLAYERS).contains(&teams) => Ok(teams),
+        _ => Err("Invalid number of teams, expected at least 1 and at most one per player"),
+    }
+}
+
 fn parse_ip(arg: &str) -> Result<std::net::IpAddr, &'static str> {
     use std::str::FromStr;
     std::net::Ipv4Addr::from_str(arg)
@@ -246,6 +660,14 @@ mod tests {
         height: 20,
         mines: 15,
         lives: 3,
+        lives_policy: LivesPolicy::Shared,
+        seed: None,
+        first_click: FirstClick::Unprotected,
+        no_guess: false,
+        layout: None,
+        topology: Topology::Rectangular,
+        mask: None,
+        undo: false,
     };
 
     #[test]
@@ -253,7 +675,7 @@ mod tests {
         let s = format!("Game starting with {CONFIG:?}!");
         assert_eq!(
             &s,
-            "Game starting with Config { width: 16, height: 20, mines: 15, lives: 3 }!"
+            "Game starting with Config { width: 16, height: 20, mines: 15, lives: 3, lives_policy: Shared, seed: None, first_click: Unprotected, no_guess: false, layout: None, topology: Rectangular, mask: None, undo: false }!"
         );
     }
 
@@ -263,6 +685,29 @@ mod tests {
         assert_eq!(&s, "Game starting with Minefield: width = 16, height = 20.\nThere are 15 mines and players have a combined total of 3 lives.!");
     }
 
+    #[test]
+    fn config_with_seed_can_be_display_formatted() {
+        let config = Config {
+            seed: Some(42),
+            ..CONFIG
+        };
+        let s = format!("{config}");
+        assert_eq!(&s, "Minefield: width = 16, height = 20.\nThere are 15 mines and players have a combined total of 3 lives.\nThe minefield is generated from seed 42.");
+    }
+
+    #[test]
+    fn config_with_lives_per_player_can_be_display_formatted() {
+        let config = Config {
+            lives_policy: LivesPolicy::PerPlayer,
+            ..CONFIG
+        };
+        let s = format!("{config}");
+        assert_eq!(
+            &s,
+            "Minefield: width = 16, height = 20.\nThere are 15 mines and every player has 3 lives."
+        );
+    }
+
     #[test]
     fn display_config_will_return_err_when_formatter_returns_err_on_first_call() {
         display_config_may_or_may_not_return_err_when_formatter_returns_err_on_nth_call(1)
@@ -1,22 +1,132 @@
 //! This crate allows for easily identifying cells within a mines field.
 //! Cells are identified using either an index or a coordinate.
-//! Currently the only kind of field that is supported is a rectangular field.
+//! The cells are laid out in rows and columns, but which cells neighbor each other depends on the
+//! topology of the field. A mask can leave out cells, to create fields of any shape.
 
-/// Any given coordinate can not have more neighboring coordinates than this number.
+use std::sync::Arc;
+
+/// No topology lets a coordinate have more neighboring coordinates than this number.
 pub const MAX_SURROUNDING_MINES: u8 = 8;
 
-/// Represents a rectangular grid.
+/// Determines the shape of the cells, and thereby which cells neighbor each other.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Topology {
+    /// Square cells with 8 neighbors each.
+    #[default]
+    Rectangular,
+    /// Hexagonal cells with 6 neighbors each. Every odd row is shifted half a cell to the right.
+    Hexagonal,
+    /// Square cells with 8 neighbors each, where the left edge touches the right edge and the top
+    /// edge touches the bottom edge. Requires at least 3 rows and 3 columns, as otherwise cells
+    /// would neighbor themselves.
+    Toroidal,
+}
+
+type Offsets = &'static [(i32, i32)];
+
+static RECTANGULAR_OFFSETS: Offsets = &[
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+static HEXAGONAL_EVEN_ROW_OFFSETS: Offsets = &[(-1, -1), (0, -1), (-1, 0), (1, 0), (-1, 1), (0, 1)];
+static HEXAGONAL_ODD_ROW_OFFSETS: Offsets = &[(0, -1), (1, -1), (-1, 0), (1, 0), (0, 1), (1, 1)];
+
+impl Topology {
+    /// Returns the maximum number of neighbors any coordinate can have.
+    pub fn max_neighbors(&self) -> u8 {
+        match self {
+            Self::Rectangular | Self::Toroidal => 8,
+            Self::Hexagonal => 6,
+        }
+    }
+
+    /// Returns the relative positions of the neighbors of a coordinate.
+    fn offsets(&self, coord: &Coord) -> Offsets {
+        match self {
+            Self::Rectangular | Self::Toroidal => RECTANGULAR_OFFSETS,
+            Self::Hexagonal => match coord.y % 2 {
+                0 => HEXAGONAL_EVEN_ROW_OFFSETS,
+                _ => HEXAGONAL_ODD_ROW_OFFSETS,
+            },
+        }
+    }
+}
+
+/// Represents a grid of rows and columns.
 ///
 /// X coordinations navigate from left to right, Y coordinations navigate from top to bottom.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct Coordinations {
     width: u32,
     height: u32,
+    topology: Topology,
+    /// Tells for every index whether or not the cell is part of the field.
+    /// Without a mask, all cells are.
+    mask: Option<Arc<[bool]>>,
 }
 
 impl Coordinations {
     pub const fn from_width_and_height(width: u32, height: u32) -> Self {
-        Self { width, height }
+        Self {
+            width,
+            height,
+            topology: Topology::Rectangular,
+            mask: None,
+        }
+    }
+
+    pub fn with_topology(self, topology: Topology) -> Self {
+        Self { topology, ..self }
+    }
+
+    /// Leaves out every cell for which the mask is `false`.
+    /// Cells that are left out never neighbor any other cell.
+    pub fn with_mask(self, mask: Vec<bool>) -> Self {
+        assert_eq!(mask.len(), self.size(), "Mask doesn't fit the field");
+        let mask = Some(mask.into());
+        Self { mask, ..self }
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn mask(&self) -> Option<&[bool]> {
+        self.mask.as_deref()
+    }
+
+    /// Determines whether or not the cell at an index is part of the field.
+    ///```
+    /// # use sioux_rust_minesweeper_crate::coordinations::*;
+    ///let c = Coordinations::from_width_and_height(2, 2).with_mask(vec![true, false, true, true]);
+    ///assert!(c.is_active(Index(0)));
+    ///assert!(!c.is_active(Index(1)));
+    ///```
+    pub fn is_active(&self, index: Index) -> bool {
+        let Index(index) = index;
+        match &self.mask {
+            Some(mask) => mask.get(index).copied().unwrap_or(false),
+            None => index < self.size(),
+        }
+    }
+
+    /// Returns the number of cells that are part of the field.
+    pub fn active_size(&self) -> usize {
+        match &self.mask {
+            Some(mask) => mask.iter().filter(|active| **active).count(),
+            None => self.size(),
+        }
+    }
+
+    /// Returns the maximum number of neighbors any coordinate within this field can have.
+    pub fn max_neighbors(&self) -> u8 {
+        self.topology.max_neighbors()
     }
 
     pub fn rows(&self) -> u32 {
@@ -27,10 +137,6 @@ impl Coordinations {
         self.width
     }
 
-    pub fn w_over_h(&self) -> f64 {
-        self.width as f64 / self.height as f64
-    }
-
     /// Returns the total number of distinct coordinates within this field.
     ///```
     /// # use sioux_rust_minesweeper_crate::coordinations::*;
@@ -71,11 +177,36 @@ impl Coordinations {
     }
 
     /// Returns an iterator over all neighboring indices.
-    pub fn neighbors_at_index(&self, index: Index) -> impl Iterator<Item = Index> {
+    /// A cell that is not part of the field has no neighbors.
+    pub fn neighbors_at_index(&self, index: Index) -> impl Iterator<Item = Index> + '_ {
         self.to_coord(index)
-            .map_or_else(NeighborsIterator::end, |coord| {
-                NeighborsIterator::new(coord, *self)
-            })
+            .filter(|_coord| self.is_active(index))
+            .map_or_else(
+                || NeighborsIterator::end(self),
+                |coord| NeighborsIterator::new(coord, self),
+            )
+    }
+
+    /// Returns the index of the coordinate at an offset from another coordinate, wrapping around
+    /// the edges if the topology requires so.
+    fn offset(&self, coord: &Coord, (dx, dy): (i32, i32)) -> Option<Index> {
+        let x = coord.x as i64 + dx as i64;
+        let y = coord.y as i64 + dy as i64;
+        let (x, y) = match self.topology {
+            Topology::Toroidal => (
+                x.rem_euclid(self.width as i64),
+                y.rem_euclid(self.height as i64),
+            ),
+            Topology::Rectangular | Topology::Hexagonal => (x, y),
+        };
+        if x < 0 || y < 0 {
+            return None;
+        }
+        let coord = Coord {
+            x: x as u32,
+            y: y as u32,
+        };
+        self.to_index(&coord).filter(|index| self.is_active(*index))
     }
 
     /// Determines whether or not a coordinate can point to an existing element within the width
@@ -85,7 +216,7 @@ impl Coordinations {
     }
 }
 
-/// Represents a location in a rectangular field using an x coordinate and an y coordinate.
+/// Represents a location in a field using an x coordinate and an y coordinate.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct Coord {
     ///starts at the left
@@ -94,85 +225,47 @@ pub struct Coord {
     pub y: u32,
 }
 
-/// Represents a location in a rectangular field using an index.
+/// Represents a location in a field using an index.
 #[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
 pub struct Index(pub usize);
 
-enum Neighbor {
-    TopLeft,
-    TopMiddle,
-    TopRight,
-    MiddleLeft,
-    MiddleRight,
-    BottomLeft,
-    BottomMiddle,
-    BottomRight,
-}
-
-impl Neighbor {
-    fn new() -> Self {
-        Self::TopLeft
-    }
-}
-
-/// An iterator over all neighboring indices.
-/// Two coordinates a and b are neighbors if |a.x-b.x| <= 1 and |a.y-b.y| <= 1 and a != b.
-pub struct NeighborsIterator {
+/// An iterator over all neighboring indices, as determined by the topology.
+pub struct NeighborsIterator<'a> {
     middle: Coord,
-    coords: Coordinations,
-    neighbor: Option<Neighbor>,
+    coords: &'a Coordinations,
+    offsets: std::slice::Iter<'static, (i32, i32)>,
 }
 
-impl NeighborsIterator {
-    fn new(middle: Coord, coords: Coordinations) -> Self {
-        let neighbor = Some(Neighbor::new());
+impl<'a> NeighborsIterator<'a> {
+    fn new(middle: Coord, coords: &'a Coordinations) -> Self {
+        let offsets = coords.topology.offsets(&middle).iter();
         Self {
             middle,
             coords,
-            neighbor,
+            offsets,
         }
     }
 
-    fn end() -> Self {
+    fn end(coords: &'a Coordinations) -> Self {
         let middle = Coord { x: 0, y: 0 };
-        let coords = Coordinations::from_width_and_height(0, 0);
-        let neighbor = None;
+        let offsets = [].iter();
         Self {
             middle,
             coords,
-            neighbor,
+            offsets,
         }
     }
 }
 
-impl std::iter::Iterator for NeighborsIterator {
+impl std::iter::Iterator for NeighborsIterator<'_> {
     type Item = Index;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let Coord { x, y } = self.middle;
-        loop {
-            let neighbor = match &self.neighbor {
-                Some(neighbor) => neighbor,
-                None => return None,
-            };
-            let (dx, dy, next): (i32, i32, Option<Neighbor>) = match neighbor {
-                Neighbor::TopLeft => (-1, -1, Some(Neighbor::TopMiddle)),
-                Neighbor::TopMiddle => (0, -1, Some(Neighbor::TopRight)),
-                Neighbor::TopRight => (1, -1, Some(Neighbor::MiddleLeft)),
-                Neighbor::MiddleLeft => (-1, 0, Some(Neighbor::MiddleRight)),
-                Neighbor::MiddleRight => (1, 0, Some(Neighbor::BottomLeft)),
-                Neighbor::BottomLeft => (-1, 1, Some(Neighbor::BottomMiddle)),
-                Neighbor::BottomMiddle => (0, 1, Some(Neighbor::BottomRight)),
-                Neighbor::BottomRight => (1, 1, None),
-            };
-            self.neighbor = next;
-            let nx: u32 = (x as i32 + dx) as u32;
-            let ny: u32 = (y as i32 + dy) as u32;
-            let n = Coord { x: nx, y: ny };
-            if let Some(index) = self.coords.to_index(&n) {
-                return Some(index);
-            }
-        }
+        let middle = self.middle;
+        let coords = self.coords;
+        self.offsets
+            .by_ref()
+            .find_map(|&offset| coords.offset(&middle, offset))
     }
 }
 
@@ -211,6 +304,10 @@ mod tests {
         // 05 06 07 08 09
         // 10 11 12 13 14
         // 15 16 17 18 19
+        correct_neighbors_within(coords, middle, neighbors)
+    }
+
+    fn correct_neighbors_within(coords: Coordinations, middle: Index, neighbors: &[Index]) {
         let mut n = coords.neighbors_at_index(middle);
         for index in neighbors {
             assert_eq!(n.next(), Some(*index));
@@ -286,4 +383,94 @@ mod tests {
     fn correct_neighbors_bottom_right() {
         correct_neighbors(Index(19), &[Index(13), Index(14), Index(18)]);
     }
+
+    fn correct_hexagonal_neighbors(middle: Index, neighbors: &[Index]) {
+        let coords = Coordinations::from_width_and_height(5, 4).with_topology(Topology::Hexagonal);
+        // indices, every odd row is shifted to the right
+        // 00 01 02 03 04
+        //   05 06 07 08 09
+        // 10 11 12 13 14
+        //   15 16 17 18 19
+        correct_neighbors_within(coords, middle, neighbors)
+    }
+
+    #[test]
+    fn correct_hexagonal_neighbors_even_row() {
+        correct_hexagonal_neighbors(
+            Index(12),
+            &[
+                Index(6),
+                Index(7),
+                Index(11),
+                Index(13),
+                Index(16),
+                Index(17),
+            ],
+        );
+    }
+
+    #[test]
+    fn correct_hexagonal_neighbors_odd_row() {
+        correct_hexagonal_neighbors(
+            Index(7),
+            &[Index(2), Index(3), Index(6), Index(8), Index(12), Index(13)],
+        );
+    }
+
+    #[test]
+    fn correct_hexagonal_neighbors_top_left() {
+        correct_hexagonal_neighbors(Index(0), &[Index(1), Index(5)]);
+    }
+
+    #[test]
+    fn correct_hexagonal_neighbors_odd_row_right() {
+        correct_hexagonal_neighbors(Index(9), &[Index(4), Index(8), Index(14)]);
+    }
+
+    #[test]
+    fn correct_toroidal_neighbors_top_left() {
+        let coords = Coordinations::from_width_and_height(5, 4).with_topology(Topology::Toroidal);
+        correct_neighbors_within(
+            coords,
+            Index(0),
+            &[
+                Index(19),
+                Index(15),
+                Index(16),
+                Index(4),
+                Index(1),
+                Index(9),
+                Index(5),
+                Index(6),
+            ],
+        );
+    }
+
+    #[test]
+    fn correct_toroidal_neighbors_center() {
+        let coords = Coordinations::from_width_and_height(5, 4).with_topology(Topology::Toroidal);
+        let toroidal: Vec<Index> = coords.neighbors_at_index(Index(7)).collect();
+        let rectangular: Vec<Index> = Coordinations::from_width_and_height(5, 4)
+            .neighbors_at_index(Index(7))
+            .collect();
+        assert_eq!(toroidal, rectangular);
+    }
+
+    #[test]
+    fn max_neighbors_follows_topology() {
+        let coords = Coordinations::from_width_and_height(5, 4);
+        assert_eq!(coords.max_neighbors(), 8);
+        assert_eq!(coords.with_topology(Topology::Hexagonal).max_neighbors(), 6);
+    }
+
+    #[test]
+    fn masked_cells_are_no_neighbors() {
+        // # # .
+        // # . #
+        let coords = Coordinations::from_width_and_height(3, 2)
+            .with_mask(vec![true, true, false, true, false, true]);
+        assert_eq!(coords.active_size(), 4);
+        correct_neighbors_within(coords.clone(), Index(1), &[Index(0), Index(3), Index(5)]);
+        correct_neighbors_within(coords, Index(4), &[]);
+    }
 }
@@ -0,0 +1,77 @@
+//! The messages which players send each other during a session.
+
+use super::*;
+
+use std::collections::VecDeque;
+
+/// The number of lines a session remembers, older lines are forgotten.
+pub const CHAT_HISTORY: usize = 100;
+/// The number of characters a single message is cut short to.
+pub const MAX_CHAT_LENGTH: usize = 200;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChatLine {
+    pub uid: SessionUserID,
+    pub text: String,
+}
+
+impl ChatLine {
+    /// Tidies up the text as typed by a player, there is no line when nothing is left.
+    pub fn new(uid: SessionUserID, text: &str) -> Option<Self> {
+        let text: String = text
+            .trim()
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(MAX_CHAT_LENGTH)
+            .collect();
+        (!text.is_empty()).then_some(Self { uid, text })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ChatHistory(VecDeque<ChatLine>);
+
+impl ChatHistory {
+    pub fn push(&mut self, line: ChatLine) {
+        let Self(lines) = self;
+        if lines.len() == CHAT_HISTORY {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// From the oldest to the newest line.
+    pub fn lines(&self) -> impl DoubleEndedIterator<Item = &ChatLine> + ExactSizeIterator {
+        let Self(lines) = self;
+        lines.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chat_lines_are_tidied_up() {
+        let uid = SessionUserID::new(2);
+        let line = ChatLine::new(uid, "  hi\tthere \n").unwrap();
+        assert_eq!(line.text, "hithere");
+        assert_eq!(ChatLine::new(uid, " \n "), None);
+        let long = "x".repeat(2 * MAX_CHAT_LENGTH);
+        let line = ChatLine::new(uid, &long).unwrap();
+        assert_eq!(line.text.len(), MAX_CHAT_LENGTH);
+    }
+
+    #[test]
+    fn chat_history_forgets_the_oldest_lines() {
+        let mut history = ChatHistory::default();
+        for i in 0..CHAT_HISTORY + 2 {
+            let line = ChatLine::new(SessionUserID::new(1), &i.to_string()).unwrap();
+            history.push(line);
+        }
+        assert_eq!(history.lines().len(), CHAT_HISTORY);
+        assert_eq!(history.lines().next().unwrap().text, "2");
+        let newest = CHAT_HISTORY + 1;
+        assert_eq!(history.lines().last().unwrap().text, newest.to_string());
+    }
+}
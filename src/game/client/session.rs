@@ -1,35 +1,50 @@
 use super::*;
+use std::time::Instant;
 
 pub struct Session {
     field: field::Field,
     stats: Stats,
+    /// When the stats were last updated, to keep the clock running in between updates.
+    updated: Instant,
 }
 
 impl Session {
-    pub fn new(coords: Coordinations, mines: Mines, lives: Lives) -> Self {
+    /// Without `lives`, the players don't share any.
+    pub fn new(coords: Coordinations, mines: Mines, lives: Option<Lives>) -> Self {
         let stats = {
             let Mines(mines_left) = mines;
-            let Lives(lives_left) = lives;
+            let lives_left = lives.map(|Lives(lives)| lives);
             Stats {
                 mines_left: mines_left as i32,
                 lives_left,
+                clock: Clock::Idle,
+                three_bv: None,
             }
         };
-        let field = field::Field::new(coords);
-        Self { field, stats }
+        Self::blank(coords, stats)
     }
 
     pub fn blank(coords: Coordinations, stats: Stats) -> Self {
         let field = field::Field::new(coords);
-        Self { field, stats }
+        let updated = Instant::now();
+        Self {
+            field,
+            stats,
+            updated,
+        }
     }
 
     pub fn stats(&self) -> Stats {
-        self.stats
+        let clock = self.stats.clock.advance(self.updated.elapsed());
+        Stats {
+            clock,
+            ..self.stats
+        }
     }
 
     fn on_stat_update(&mut self, stats: Stats) {
         self.stats = stats;
+        self.updated = Instant::now();
     }
 }
 
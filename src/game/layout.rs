@@ -0,0 +1,318 @@
+//! Mine layout generators decide which cells of a minefield hold a mine.
+//! Implement `MineLayoutGenerator` to play on custom layouts, such as tournament boards or
+//! teaching puzzles, and hand it to a session with `Generator::Custom`.
+
+use super::*;
+use crate::game::server::solver;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use std::sync::{Arc, Mutex};
+
+pub trait MineLayoutGenerator: Send {
+    /// Returns for every index within `coords` whether or not it holds a mine, `mines` mines in
+    /// total, or a mine in every cell when they don't fit.
+    /// The `safe` indices should only receive a mine when there is no room left elsewhere.
+    /// When there are `safe` indices, the first one is the cell that gets uncovered first.
+    fn generate(&mut self, coords: &Coordinations, mines: Mines, safe: &[Index]) -> Vec<bool>;
+}
+
+/// A generator which can be shared by all sessions that are played with the same config.
+pub type SharedGenerator = Arc<Mutex<dyn MineLayoutGenerator>>;
+
+/// Lays out the mines randomly, using a different seed every time.
+/// The seed is printed, so that the minefield can be replayed with `SeededLayout`.
+pub struct RandomLayout;
+
+impl MineLayoutGenerator for RandomLayout {
+    fn generate(&mut self, coords: &Coordinations, mines: Mines, safe: &[Index]) -> Vec<bool> {
+        let seed = Seed::random();
+        let Seed(s) = seed;
+        println!("Minefield seed: {s}");
+        SeededLayout(seed).generate(coords, mines, safe)
+    }
+}
+
+/// Lays out the mines randomly. The same seed always results in the same layout.
+pub struct SeededLayout(pub Seed);
+
+impl MineLayoutGenerator for SeededLayout {
+    fn generate(&mut self, coords: &Coordinations, mines: Mines, safe: &[Index]) -> Vec<bool> {
+        let Self(Seed(seed)) = *self;
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        random(coords, mines, &mut rng, safe)
+    }
+}
+
+/// Keeps generating random layouts until the solver can clear one without guessing, starting at
+/// the first `safe` index. Falls back to the last random layout when the budget runs out, or when
+/// there are no `safe` indices to start from.
+pub struct NoGuessLayout {
+    pub seed: Seed,
+    pub budget: NoGuessBudget,
+}
+
+impl MineLayoutGenerator for NoGuessLayout {
+    fn generate(&mut self, coords: &Coordinations, mines: Mines, safe: &[Index]) -> Vec<bool> {
+        let Self {
+            seed: Seed(seed),
+            budget: NoGuessBudget { attempts, time },
+        } = *self;
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let first = match safe.first() {
+            Some(first) => *first,
+            None => return random(coords, mines, &mut rng, safe),
+        };
+        let start = std::time::Instant::now();
+        let mut attempt = 0;
+        loop {
+            let layout = random(coords, mines, &mut rng, safe);
+            attempt += 1;
+            if solver::solvable(coords, &layout, first) {
+                return layout;
+            }
+            if attempt >= attempts || start.elapsed() >= time {
+                println!("No minefield without guessing found after {attempt} attempts, playing a random one");
+                return layout;
+            }
+        }
+    }
+}
+
+/// A fixed layout, read from text where every line is a row of the minefield.
+/// A `*` is a mine and a `.` is not.
+/// The layout always stays the same, so it ignores which cells should be safe.
+#[derive(Clone, Debug)]
+pub struct FileLayout {
+    coords: Coordinations,
+    mines: Vec<bool>,
+}
+
+impl FileLayout {
+    pub fn load(path: &std::path::Path) -> Result<Self, &'static str> {
+        std::fs::read_to_string(path)
+            .map_err(|_| "Failed to read layout file")?
+            .parse()
+    }
+
+    pub fn coords(&self) -> Coordinations {
+        self.coords.clone()
+    }
+
+    pub fn mines(&self) -> Mines {
+        Mines(self.mines.iter().filter(|mine| **mine).count() as u32)
+    }
+
+    /// Tells whether the layout can be played on the given minefield.
+    pub fn check(&self, coords: &Coordinations) -> Result<(), &'static str> {
+        if (coords.columns(), coords.rows()) != (self.coords.columns(), self.coords.rows()) {
+            return Err("Layout doesn't fit the minefield");
+        }
+        if self
+            .mines
+            .iter()
+            .enumerate()
+            .any(|(index, mine)| *mine && !coords.is_active(Index(index)))
+        {
+            return Err("Layout has mines outside of the minefield");
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for FileLayout {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (coords, mines) = parse_rows(s, |c| match c {
+            '*' => Ok(true),
+            '.' => Ok(false),
+            _ => Err("Layout may only contain '*' and '.'"),
+        })?;
+        Ok(Self { coords, mines })
+    }
+}
+
+/// Parses text where every line is a row of the minefield, and every character a cell.
+pub(super) fn parse_rows<F>(s: &str, cell: F) -> Result<(Coordinations, Vec<bool>), &'static str>
+where
+    F: Fn(char) -> Result<bool, &'static str>,
+{
+    let rows: Vec<&str> = s
+        .lines()
+        .map(str::trim)
+        .filter(|row| !row.is_empty())
+        .collect();
+    let width = rows.first().ok_or("Text has no rows")?.len();
+    if rows.iter().any(|row| row.len() != width) {
+        return Err("Text rows differ in length");
+    }
+    let cells = rows
+        .iter()
+        .flat_map(|row| row.chars())
+        .map(cell)
+        .collect::<Result<Vec<bool>, _>>()?;
+    let coords = Coordinations::from_width_and_height(width as u32, rows.len() as u32);
+    Ok((coords, cells))
+}
+
+impl MineLayoutGenerator for FileLayout {
+    fn generate(&mut self, coords: &Coordinations, mines: Mines, _safe: &[Index]) -> Vec<bool> {
+        self.check(coords)
+            .expect("Layout was checked against the minefield");
+        let Mines(mines) = mines;
+        let Mines(layout_mines) = self.mines();
+        assert_eq!(
+            mines, layout_mines,
+            "Layout has a different number of mines"
+        );
+        self.mines.clone()
+    }
+}
+
+/// Lays out the mines once, and hands out that same layout every time after, so that several
+/// minefields get the very same mines. Only the first uncovered cell of all of them is kept safe.
+#[derive(Clone)]
+pub struct ReusedLayout(Arc<Mutex<Reused>>);
+
+struct Reused {
+    generator: Box<dyn MineLayoutGenerator>,
+    layout: Option<Vec<bool>>,
+}
+
+impl ReusedLayout {
+    pub fn new(generator: Box<dyn MineLayoutGenerator>) -> Self {
+        let reused = Reused {
+            generator,
+            layout: None,
+        };
+        Self(Arc::new(Mutex::new(reused)))
+    }
+}
+
+impl MineLayoutGenerator for ReusedLayout {
+    fn generate(&mut self, coords: &Coordinations, mines: Mines, safe: &[Index]) -> Vec<bool> {
+        let Self(reused) = self;
+        let Reused { generator, layout } =
+            &mut *reused.lock().expect("Failed to lock mine layout generator");
+        layout
+            .get_or_insert_with(|| generator.generate(coords, mines, safe))
+            .clone()
+    }
+}
+
+impl MineLayoutGenerator for SharedGenerator {
+    fn generate(&mut self, coords: &Coordinations, mines: Mines, safe: &[Index]) -> Vec<bool> {
+        self.lock()
+            .expect("Failed to lock mine layout generator")
+            .generate(coords, mines, safe)
+    }
+}
+
+/// ChaCha8 is used because, unlike `StdRng`, its output is guaranteed to be the same on every
+/// platform and for every release of `rand_chacha`.
+///
+/// The `safe` indices only receive a mine when there is no room left elsewhere, the first of them
+/// being the very last one to receive a mine.
+fn random<R>(coords: &Coordinations, mines: Mines, rng: &mut R, safe: &[Index]) -> Vec<bool>
+where
+    R: Rng,
+{
+    let Mines(number_of_mines) = mines;
+    let mut indices: Vec<Index> = (0..coords.size())
+        .map(Index)
+        .filter(|index| coords.is_active(*index) && !safe.contains(index))
+        .collect();
+    indices.shuffle(rng);
+    indices.splice(0..0, safe.iter().copied());
+    let mut mines = vec![false; coords.size()];
+    for Index(index) in indices.into_iter().rev().take(number_of_mines as usize) {
+        mines[index] = true;
+    }
+    mines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COORDS: Coordinations = Coordinations::from_width_and_height(16, 12);
+
+    #[test]
+    fn same_seed_gives_same_layout() {
+        let a = SeededLayout(Seed(1234)).generate(&COORDS, Mines(30), &[]);
+        let b = SeededLayout(Seed(1234)).generate(&COORDS, Mines(30), &[]);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn different_seeds_give_different_layouts() {
+        let a = SeededLayout(Seed(1234)).generate(&COORDS, Mines(30), &[]);
+        let b = SeededLayout(Seed(4321)).generate(&COORDS, Mines(30), &[]);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn seeded_layout_has_requested_number_of_mines() {
+        let layout = SeededLayout(Seed(99)).generate(&COORDS, Mines(30), &[]);
+        assert_eq!(layout.iter().filter(|mine| **mine).count(), 30);
+    }
+
+    #[test]
+    fn seeded_layout_fills_every_cell_with_too_many_mines() {
+        let safe = [Index(0)];
+        let layout = SeededLayout(Seed(99)).generate(&COORDS, Mines(1000), &safe);
+        assert!(layout.iter().all(|mine| *mine));
+    }
+
+    #[test]
+    fn no_guess_layout_can_be_solved() {
+        let safe: Vec<Index> = std::iter::once(Index(0))
+            .chain(COORDS.neighbors_at_index(Index(0)))
+            .collect();
+        let mut generator = NoGuessLayout {
+            seed: Seed(5),
+            budget: NoGuessBudget::default(),
+        };
+        let layout = generator.generate(&COORDS, Mines(30), &safe);
+        assert!(solver::solvable(&COORDS, &layout, Index(0)));
+    }
+
+    #[test]
+    fn file_layout_is_parsed() {
+        let layout: FileLayout = "..*\n*..\n".parse().unwrap();
+        assert_eq!(layout.coords().columns(), 3);
+        assert_eq!(layout.coords().rows(), 2);
+        let Mines(mines) = layout.mines();
+        assert_eq!(mines, 2);
+        let mut layout = layout;
+        let coords = layout.coords();
+        assert_eq!(
+            layout.generate(&coords, Mines(2), &[]),
+            vec![false, false, true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn file_layout_rejects_ragged_rows() {
+        assert!("..*\n*.\n".parse::<FileLayout>().is_err());
+    }
+
+    #[test]
+    fn file_layout_is_checked_against_the_minefield() {
+        let layout: FileLayout = "..*\n*..\n".parse().unwrap();
+        assert!(layout.check(&layout.coords()).is_ok());
+        let larger = Coordinations::from_width_and_height(3, 3);
+        assert!(layout.check(&larger).is_err());
+        let masked = layout
+            .coords()
+            .with_mask(vec![true, true, true, false, true, true]);
+        assert!(layout.check(&masked).is_err());
+        let masked = layout
+            .coords()
+            .with_mask(vec![false, true, true, true, true, true]);
+        assert!(layout.check(&masked).is_ok());
+    }
+}
@@ -55,4 +55,17 @@ where
         let updates = self.server.toggle_mark(coord, self.uid);
         self.on_click(updates)
     }
+
+    fn on_chord(&mut self, coord: &Coord) {
+        let updates = self.server.chord(coord, self.uid);
+        self.on_click(updates)
+    }
+
+    fn on_undo(&mut self) {
+        let updates = self.server.undo();
+        self.on_click(updates)
+    }
+
+    /// There is no one to chat with.
+    fn on_chat(&mut self, _text: &str) {}
 }
@@ -6,6 +6,8 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 /// Contains all data required to run a single-player game
+const MY_UID: SessionUserID = SessionUserID(1);
+
 pub struct Session {
     server: server::Server<ClientSessionCell>,
     client: ClientSessionCell,
@@ -14,16 +16,13 @@ pub struct Session {
 
 impl Session {
     pub fn new(config: &game::session::SessionConfig) -> Self {
-        let game::session::SessionConfig {
-            coords,
-            mines,
-            lives,
-        } = *config;
-        let client = game::client::session::Session::new(coords, mines, lives);
+        let game::session::SessionConfig { coords, mines, .. } = config;
+        let client =
+            game::client::session::Session::new(coords.clone(), *mines, config.shared_lives());
         let client = ClientSessionCell::new(client);
-        let server = game::server::session::Session::new(coords, mines, lives);
+        let server = game::server::session::Session::new(config);
         let local_updates_listener = client.clone();
-        let server = server::Server::new(server, local_updates_listener, SessionUserID::new(1));
+        let server = server::Server::new(server, local_updates_listener, MY_UID);
         let namer = LocalSessionNamer;
         Self {
             server,
@@ -46,6 +45,11 @@ impl game::session::Session for Session {
             user_stats,
             status,
             coords,
+            players: vec![(MY_UID, Connection::Connected)],
+            spectators: Vec::new(),
+            teams: None,
+            chat: None,
+            connection: Connection::Connected,
             namer: &self.namer,
             field_provider: &self.client,
             local_player_listener: &mut self.server,
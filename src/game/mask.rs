@@ -0,0 +1,86 @@
+//! Masks leave out cells of the minefield, to play on boards with holes or of any shape.
+
+use super::*;
+
+/// Tells for every cell of a rectangle whether or not it is part of the minefield.
+#[derive(Clone, Debug)]
+pub struct Mask {
+    coords: Coordinations,
+    cells: Vec<bool>,
+}
+
+impl Mask {
+    /// Loads a mask from an image when the file is a PNG, or from text otherwise.
+    pub fn load(path: &std::path::Path) -> Result<Self, &'static str> {
+        let is_png = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        if is_png {
+            let image = image::open(path).map_err(|_| "Failed to read mask image")?;
+            Ok(Self::from_image(&image))
+        } else {
+            std::fs::read_to_string(path)
+                .map_err(|_| "Failed to read mask file")?
+                .parse()
+        }
+    }
+
+    /// Every pixel is a cell. Only opaque pixels which are darker than gray are part of the
+    /// minefield.
+    pub fn from_image(image: &image::DynamicImage) -> Self {
+        let image = image.to_luma_alpha8();
+        let (width, height) = image.dimensions();
+        let cells = image
+            .pixels()
+            .map(|image::LumaA([luma, alpha])| *luma < 128 && *alpha >= 128)
+            .collect();
+        let coords = Coordinations::from_width_and_height(width, height);
+        Self { coords, cells }
+    }
+
+    /// Returns a field of the size of the mask, without the cells that are left out.
+    pub fn coords(&self) -> Coordinations {
+        self.coords.clone().with_mask(self.cells.clone())
+    }
+}
+
+/// Text where every line is a row of the minefield.
+/// A `#` is part of the minefield and a `.` is not.
+impl std::str::FromStr for Mask {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (coords, cells) = layout::parse_rows(s, |c| match c {
+            '#' => Ok(true),
+            '.' => Ok(false),
+            _ => Err("Mask may only contain '#' and '.'"),
+        })?;
+        Ok(Self { coords, cells })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mask_is_parsed() {
+        let mask: Mask = "##.\n#.#\n".parse().unwrap();
+        let coords = mask.coords();
+        assert_eq!(coords.columns(), 3);
+        assert_eq!(coords.rows(), 2);
+        assert_eq!(coords.active_size(), 4);
+        assert!(!coords.is_active(Index(2)));
+    }
+
+    #[test]
+    fn mask_from_image_keeps_dark_pixels() {
+        let black = image::Rgba([0, 0, 0, 255]);
+        let white = image::Rgba([255, 255, 255, 255]);
+        let transparent = image::Rgba([0, 0, 0, 0]);
+        let pixels = [black, white, transparent];
+        let image = image::RgbaImage::from_fn(3, 1, |x, _y| pixels[x as usize]);
+        let coords = Mask::from_image(&image::DynamicImage::ImageRgba8(image)).coords();
+        assert_eq!(coords.mask(), Some(&[true, false, false][..]));
+    }
+}
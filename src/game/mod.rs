@@ -1,5 +1,8 @@
+pub mod chat;
 mod client;
+pub mod layout;
 pub mod local;
+pub mod mask;
 pub mod multiplayer;
 mod server;
 pub mod session;
@@ -9,6 +12,7 @@ use crate::coordinations::*;
 use types::*;
 
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub struct SessionUserID(u8);
@@ -24,6 +28,14 @@ impl SessionUserID {
     }
 }
 
+/// Whether or not a player can still be reached.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Connection {
+    #[default]
+    Connected,
+    Lost,
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct Hint(pub u8);
 
@@ -33,6 +45,69 @@ pub struct Mines(pub u32);
 #[derive(Clone, Copy)]
 pub struct Lives(pub u32);
 
+/// Determines whose lives an explosion costs.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LivesPolicy {
+    /// The players share their lives, once those are gone the session is lost.
+    #[default]
+    Shared,
+    /// Every player has lives of their own, and can only watch once those are gone. The session
+    /// is lost once everyone who played ran out of lives.
+    PerPlayer,
+    /// Explosions never end the session, but count against the player who caused them.
+    Unlimited,
+}
+
+/// Determines the layout of the mines. The same seed, together with the same dimensions and
+/// number of mines, always results in the same minefield.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Seed(pub u64);
+
+impl Seed {
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+}
+
+/// Determines how the first uncovered cell is protected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FirstClick {
+    /// All mines are laid out before the first click, so it can hit a mine.
+    Unprotected,
+    /// The mines are laid out on the first click, never on the clicked cell.
+    Safe,
+    /// Like `Safe`, but the neighbors of the clicked cell are kept free of mines as well, so the
+    /// first click always opens an area.
+    Opening,
+}
+
+/// Determines how the mines are laid out.
+#[derive(Clone)]
+pub enum Generator {
+    Random,
+    /// Only accepts minefields which can be cleared from the first click onwards without ever
+    /// having to guess. The first click always opens an area.
+    NoGuess(NoGuessBudget),
+    Custom(layout::SharedGenerator),
+}
+
+/// Limits the search for a minefield without guessing. When it runs out, a random minefield is
+/// used instead.
+#[derive(Clone, Copy, Debug)]
+pub struct NoGuessBudget {
+    pub attempts: u32,
+    pub time: std::time::Duration,
+}
+
+impl Default for NoGuessBudget {
+    fn default() -> Self {
+        Self {
+            attempts: 10_000,
+            time: std::time::Duration::from_secs(2),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Cell {
     Covered,
@@ -49,15 +124,100 @@ pub struct UserStat {
     pub marked_correct: u32,
     pub marked_incorrect: u32,
     pub exploded: u32,
+    /// The cells without a mine which got uncovered.
+    pub revealed: u32,
+    /// The uncovered cells with mines around them.
+    pub hints: u32,
+    /// How many times an area got opened at once.
+    pub openings: u32,
+    /// The number of cells uncovered by the largest opening.
+    pub biggest_opening: u32,
+    /// The clicks which changed something on the minefield.
+    pub effective_clicks: u32,
+    /// The clicks which changed nothing.
+    pub wasted_clicks: u32,
+    /// Only when every player has lives of their own.
+    pub lives_left: Option<u32>,
+    pub score: i32,
 }
 
 #[derive(Clone, Default, Debug)]
 pub struct UserStats(pub HashMap<SessionUserID, UserStat>);
 
+/// One of the sides which race each other, every team on a minefield of its own.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Team(pub u8);
+
+impl std::fmt::Display for Team {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Team(team) = self;
+        write!(fmt, "Team {}", team + 1)
+    }
+}
+
+/// How far a team got on its minefield.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TeamProgress {
+    pub team: Team,
+    pub players: Vec<SessionUserID>,
+    pub uncovered: u32,
+    /// The number of cells without a mine, which all have to be uncovered.
+    pub safe: u32,
+    /// Only when the players share their lives.
+    pub lives_left: Option<u32>,
+}
+
+/// How the teams are doing, when teams compete.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Standings {
+    /// The team of which the minefield is shown.
+    pub shown: Team,
+    pub teams: Vec<TeamProgress>,
+    /// Known once the session has ended, none when every team lost.
+    pub winner: Option<Team>,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Stats {
     pub mines_left: i32,
-    pub lives_left: u32,
+    /// Only when the players share their lives.
+    pub lives_left: Option<u32>,
+    pub clock: Clock,
+    /// The minimum number of clicks which clear the minefield, once the mines are laid out.
+    pub three_bv: Option<u32>,
+}
+
+/// The time spent playing a session, which starts running at the first uncover.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Clock {
+    #[default]
+    Idle,
+    Running(Duration),
+    Stopped(Duration),
+}
+
+impl Clock {
+    pub fn elapsed(&self) -> Duration {
+        match *self {
+            Clock::Idle => Duration::ZERO,
+            Clock::Running(elapsed) | Clock::Stopped(elapsed) => elapsed,
+        }
+    }
+
+    /// Moves a running clock `time` further, other clocks stay as they are.
+    pub fn advance(self, time: Duration) -> Self {
+        match self {
+            Clock::Running(elapsed) => Clock::Running(elapsed + time),
+            clock => clock,
+        }
+    }
+}
+
+/// When a player first and last acted upon the field, as the clock of the session showed.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Activity {
+    pub first: Duration,
+    pub last: Duration,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -78,6 +238,11 @@ impl Status {
 pub trait LocalPlayerListener {
     fn on_left_click(&mut self, coord: &Coord);
     fn on_right_click(&mut self, coord: &Coord);
+    fn on_chord(&mut self, coord: &Coord);
+    /// Reverses the last move, if moves can be undone.
+    fn on_undo(&mut self);
+    /// Sends a message to the other players, if there are any.
+    fn on_chat(&mut self, text: &str);
 }
 
 pub trait FieldProvider {
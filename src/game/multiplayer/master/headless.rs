@@ -0,0 +1,201 @@
+//! A master without a window or a player of its own, which only serves the slaves.
+//! It plays round after round with the same slaves, and logs what happens to stdout.
+
+use super::*;
+
+use std::sync;
+
+/// How often the master checks whether a round has ended.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+pub struct Headless {
+    master: std::net::SocketAddr,
+    transport: Transport,
+    codec: Codec,
+    timeout: std::time::Duration,
+    config: game::session::SessionConfig,
+    teams: u8,
+}
+
+impl Headless {
+    pub fn new(
+        master: std::net::SocketAddr,
+        transport: Transport,
+        codec: Codec,
+        timeout: std::time::Duration,
+        config: game::session::SessionConfig,
+        teams: u8,
+    ) -> Self {
+        Self {
+            master,
+            transport,
+            codec,
+            timeout,
+            config,
+            teams,
+        }
+    }
+
+    /// Starts a new round whenever one ends, until the process is stopped. The slaves stay
+    /// connected from one round to the next.
+    pub fn serve(&self) -> ! {
+        let channel = self.transport.listen(self.master);
+        let messenger = sync::Arc::new(Messenger::new(channel, self.codec, self.timeout));
+        let server = {
+            let sessions = server::team_sessions(&self.config, self.teams);
+            let server = server::Server::headless(sessions, messenger.clone());
+            let server = sync::Mutex::new(server);
+            sync::Arc::new(server)
+        };
+        let slave_listener = LoggingSlaveListener {
+            server: sync::Arc::downgrade(&server),
+        };
+        let _messenger_thread = messenger_thread::spawn_read_messages_from_slaves_to_master_thread(
+            sync::Arc::downgrade(&messenger),
+            slave_listener,
+        );
+        let mut round = 1u64;
+        loop {
+            println!("Round {round} starts");
+            self.play_round(&server);
+            // everyone gets to see how the round ended, before the minefield is replaced
+            let start = std::time::Instant::now();
+            while !messenger.flushed() && start.elapsed() < self.timeout {
+                std::thread::sleep(POLL_INTERVAL);
+            }
+            round += 1;
+            let sessions = server::team_sessions(&self.config, self.teams);
+            server
+                .lock()
+                .expect("Failed to lock multiplayer server session")
+                .start_round(sessions);
+        }
+    }
+
+    /// Waits for the round to end, and logs the result.
+    fn play_round(&self, server: &sync::Mutex<server::Server>) {
+        loop {
+            let server = server
+                .lock()
+                .expect("Failed to lock multiplayer server session");
+            if let Status::Ended { success } = server.status() {
+                log_result(&server, success);
+                return;
+            }
+            drop(server);
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+fn log_result(server: &server::Server, success: bool) {
+    match success {
+        true => println!("Round won"),
+        false => println!("Round lost"),
+    }
+    if let Some(three_bv) = server.three_bv() {
+        println!("3BV: {three_bv}");
+    }
+    let UserStats(stats) = server.user_stats();
+    let activities = server.activities();
+    let UserNames(names) = server.names();
+    for UserName { uid, name, .. } in names {
+        let UserStat {
+            marked_correct,
+            marked_incorrect,
+            exploded,
+            revealed,
+            hints,
+            openings,
+            biggest_opening,
+            effective_clicks,
+            wasted_clicks,
+            lives_left: _,
+            score,
+        } = stats.get(&uid).copied().unwrap_or_default();
+        println!(
+            "{name}: {score} points, {marked_correct} marked correctly, {marked_incorrect} marked incorrectly, {exploded} exploded"
+        );
+        println!(
+            "{name}: {revealed} cells uncovered, {hints} hints, {openings} openings of which the biggest had {biggest_opening} cells"
+        );
+        println!("{name}: {effective_clicks} effective clicks, {wasted_clicks} wasted clicks");
+        if let Some(Activity { first, last }) = activities.get(&uid) {
+            println!("{name}: active from {first:?} until {last:?}");
+        }
+    }
+    if let Some(Standings { teams, winner, .. }) = server.standings() {
+        for TeamProgress {
+            team,
+            uncovered,
+            safe,
+            lives_left,
+            ..
+        } in teams
+        {
+            let won = match winner == Some(team) {
+                true => ", the winner",
+                false => "",
+            };
+            let lives = match lives_left {
+                Some(lives_left) => format!(", {lives_left} lives left"),
+                None => String::new(),
+            };
+            println!("{team}: {uncovered} of {safe} uncovered{lives}{won}");
+        }
+    }
+}
+
+struct LoggingSlaveListener {
+    server: sync::Weak<sync::Mutex<server::Server>>,
+}
+
+impl SlaveListener for LoggingSlaveListener {
+    fn on_request_to_join(&mut self, request: RequestFromSlave, addr: std::net::SocketAddr) {
+        if let Some(server) = self.server.upgrade() {
+            server
+                .lock()
+                .expect("Failed to lock multiplayer server session")
+                .on_request_to_join(request, addr)
+        }
+    }
+
+    fn on_action_from_slave(&mut self, action: ActionFromSlave, addr: std::net::SocketAddr) {
+        if let Some(server) = self.server.upgrade() {
+            let mut server = server
+                .lock()
+                .expect("Failed to lock multiplayer server session");
+            let Coord { x, y } = action.coord;
+            let what = match action.action {
+                Action::Uncover => "uncovered",
+                Action::ToggleMark => "toggled the mark at",
+                Action::Chord => "chorded at",
+            };
+            let name = server.name_at(&addr).map(String::from);
+            if let (Some(_), Some(name)) = (server.on_action_from_slave(action, addr), name) {
+                println!("{name} {what} ({x}, {y})");
+            }
+        }
+    }
+
+    fn on_chat_from_slave(&mut self, text: String, addr: std::net::SocketAddr) {
+        if let Some(server) = self.server.upgrade() {
+            let mut server = server
+                .lock()
+                .expect("Failed to lock multiplayer server session");
+            let name = server.name_at(&addr).map(String::from);
+            if let (Some(line), Some(name)) = (server.on_chat_from_slave(&text, addr), name) {
+                println!("{name}: {}", line.text);
+            }
+        }
+    }
+
+    fn on_slave_lost(&mut self, addr: std::net::SocketAddr) {
+        if let Some(server) = self.server.upgrade() {
+            server
+                .lock()
+                .expect("Failed to lock multiplayer server session")
+                .on_slave_lost(addr)
+        }
+    }
+}
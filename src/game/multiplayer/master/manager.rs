@@ -5,19 +5,32 @@ use std::sync;
 pub struct Manager {
     name: String,
     master: std::net::SocketAddr,
+    transport: Transport,
+    codec: Codec,
+    timeout: std::time::Duration,
     config: game::session::SessionConfig,
+    /// The number of teams which race each other.
+    teams: u8,
 }
 
 impl Manager {
     pub fn new(
         name: String,
         master: std::net::SocketAddr,
+        transport: Transport,
+        codec: Codec,
+        timeout: std::time::Duration,
         config: game::session::SessionConfig,
+        teams: u8,
     ) -> Self {
         Self {
             name,
             master,
+            transport,
+            codec,
+            timeout,
             config,
+            teams,
         }
     }
 }
@@ -26,22 +39,18 @@ impl game::session::SessionManager for Manager {
     type Session = MultiplayerSession;
 
     fn request_new_session(&self) -> Self::Session {
-        let remote =
-            std::net::UdpSocket::bind(self.master).expect("Failed to connect to master server");
-        let messenger = sync::Arc::new(Messenger::new(remote));
-        let game::session::SessionConfig {
-            coords,
-            mines,
-            lives,
-        } = self.config;
+        let channel = self.transport.listen(self.master);
+        let messenger = sync::Arc::new(Messenger::new(channel, self.codec, self.timeout));
+        let game::session::SessionConfig { coords, mines, .. } = &self.config;
         let client = {
-            let client = game::client::session::Session::new(coords, mines, lives);
+            let lives = self.config.shared_lives();
+            let client = game::client::session::Session::new(coords.clone(), *mines, lives);
             let client = sync::Mutex::new(client);
             sync::Arc::new(client)
         };
         let server = {
-            let server = game::server::session::Session::new(coords, mines, lives);
-            let server = server::Server::new(server, messenger.clone(), self.name.clone());
+            let sessions = server::team_sessions(&self.config, self.teams);
+            let server = server::Server::new(sessions, messenger.clone(), self.name.clone());
             let server = sync::Mutex::new(server);
             sync::Arc::new(server)
         };
@@ -86,10 +95,15 @@ impl SlaveListener for MySlaveListener {
 
     fn on_action_from_slave(&mut self, action: ActionFromSlave, addr: std::net::SocketAddr) {
         if let Some(server) = self.server.upgrade() {
-            let updates = server
+            let mut server = server
                 .lock()
-                .expect("Failed to lock multiplayer server session")
-                .on_action_from_slave(action, addr);
+                .expect("Failed to lock multiplayer server session");
+            let my_team = server.my_team();
+            // only the minefield of the own team is shown
+            let updates = server
+                .on_action_from_slave(action, addr)
+                .and_then(|(team, updates)| (Some(team) == my_team).then_some(updates));
+            drop(server);
             if let Some(updates) = updates {
                 if let Some(client) = self.client.upgrade() {
                     client
@@ -100,4 +114,22 @@ impl SlaveListener for MySlaveListener {
             }
         }
     }
+
+    fn on_chat_from_slave(&mut self, text: String, addr: std::net::SocketAddr) {
+        if let Some(server) = self.server.upgrade() {
+            server
+                .lock()
+                .expect("Failed to lock multiplayer server session")
+                .on_chat_from_slave(&text, addr);
+        }
+    }
+
+    fn on_slave_lost(&mut self, addr: std::net::SocketAddr) {
+        if let Some(server) = self.server.upgrade() {
+            server
+                .lock()
+                .expect("Failed to lock multiplayer server session")
+                .on_slave_lost(addr)
+        }
+    }
 }
@@ -32,6 +32,10 @@ fn thread_fn<L>(
     ) {
         let messenger = &messenger;
         thread_cycle(messenger, &mut buffer, &mut listener);
+        messenger.retransmit();
+        for addr in messenger.check_peers() {
+            listener.on_slave_lost(addr);
+        }
     }
 }
 
@@ -51,5 +55,6 @@ where
         MessageFromSlave::Action(action) => {
             listener.on_action_from_slave(action, addr);
         }
+        MessageFromSlave::Chat(text) => listener.on_chat_from_slave(text, addr),
     }
 }
@@ -1,3 +1,4 @@
+mod headless;
 mod manager;
 mod messenger_thread;
 mod server;
@@ -7,4 +8,5 @@ mod types;
 use super::*;
 use types::*;
 
+pub use headless::Headless;
 pub use manager::*;
@@ -7,28 +7,146 @@ pub struct Server {
     players: players::Players,
 }
 
+/// Creates a session for every team, all with the same minefield. The mines are laid out at the
+/// first click of any team, so that first click is the only one which is kept safe.
+pub fn team_sessions(
+    config: &game::session::SessionConfig,
+    teams: u8,
+) -> Vec<game::server::session::Session> {
+    let (config, generator) = game::server::session::Session::generator(config);
+    let generator = game::layout::ReusedLayout::new(generator);
+    (0..teams.max(1))
+        .map(|_| {
+            game::server::session::Session::with_generator(&config, Box::new(generator.clone()))
+        })
+        .collect()
+}
+
 impl Server {
     pub fn new(
-        server: game::server::session::Session,
+        sessions: Vec<game::server::session::Session>,
         messenger: std::sync::Arc<Messenger>,
         name: String,
     ) -> Self {
         let my_uid = SessionUserID(1);
-        let core = Core::new(server, messenger, my_uid);
-        let players = players::Players::new(my_uid, name);
+        let teams = sessions.len() as u8;
+        let mut core = Core::new(sessions, messenger, Some(my_uid));
+        let players = players::Players::new(my_uid, name, teams);
+        if let Some(team) = players.my_team() {
+            core.session_mut(team).join(my_uid);
+        }
+        Self { core, players }
+    }
+
+    /// A master which only serves the slaves, without a player of its own.
+    pub fn headless(
+        sessions: Vec<game::server::session::Session>,
+        messenger: std::sync::Arc<Messenger>,
+    ) -> Self {
+        let teams = sessions.len() as u8;
+        let core = Core::new(sessions, messenger, None);
+        let players = players::Players::headless(teams);
         Self { core, players }
     }
 
+    /// Starts a new round on `sessions`, and hands every player and spectator who is still
+    /// connected the new minefield. Players who lost their connection are forgotten, as their
+    /// moves went with the round before.
+    pub fn start_round(&mut self, sessions: Vec<game::server::session::Session>) {
+        self.core.start_round(sessions);
+        self.players.forget_lost();
+        for (uid, _) in self.players.names().players() {
+            let team = self.players.team_of(uid);
+            self.core.session_mut(team).join(uid);
+        }
+        for (addr, uid) in self.players.recipients() {
+            let team = self.players.shown_team(uid);
+            let cells = self.core.session(team).all();
+            let update = create_update_message(&self.core, &self.players, team, cells);
+            self.core
+                .messenger
+                .send_updates_from_master(&mut self.core.messenger_buffer, addr, uid, update)
+                .unwrap();
+        }
+    }
+
     pub fn status(&self) -> Status {
-        self.core.server.status()
+        match self.players.my_team() {
+            Some(team) => self.core.status(team),
+            // without a player of its own, a round is won when any team wins it
+            None => match self.core.ended() {
+                false => Status::Playing,
+                true => Status::Ended {
+                    success: self.core.winner().is_some(),
+                },
+            },
+        }
     }
 
     pub fn coords(&self) -> Coordinations {
-        self.core.server.coords()
+        self.core.session(Team(0)).coords()
+    }
+
+    /// The minimum number of clicks which clear the minefield of the first team, once its mines
+    /// are laid out.
+    pub fn three_bv(&self) -> Option<u32> {
+        self.core.session(Team(0)).stats().three_bv
     }
 
     pub fn user_stats(&self) -> UserStats {
-        self.core.server.user_stats()
+        self.core.user_stats()
+    }
+
+    /// When the players of every team were active, on the clock of their own team.
+    pub fn activities(&self) -> HashMap<SessionUserID, Activity> {
+        self.core
+            .sessions
+            .iter()
+            .flat_map(|session| session.activities())
+            .collect()
+    }
+
+    /// How the teams are doing, seen from the team of the player at the master.
+    pub fn standings(&self) -> Option<Standings> {
+        let team = self.players.shown_team(self.core.my_uid);
+        self.core.standings(&self.players, team)
+    }
+
+    pub fn my_team(&self) -> Option<Team> {
+        self.players.my_team()
+    }
+
+    pub fn players(&self) -> Vec<(SessionUserID, Connection)> {
+        self.players.names().players()
+    }
+
+    pub fn spectators(&self) -> Vec<String> {
+        self.players.spectator_names()
+    }
+
+    pub fn names(&self) -> UserNames {
+        self.players.names()
+    }
+
+    pub fn chat(&self) -> chat::ChatHistory {
+        self.core.chat.clone()
+    }
+
+    /// Relays what the player at `addr` said, spectators can't chat.
+    pub fn on_chat_from_slave(
+        &mut self,
+        text: &str,
+        addr: std::net::SocketAddr,
+    ) -> Option<chat::ChatLine> {
+        let uid = self.players.get_uid(&addr)?;
+        let line = chat::ChatLine::new(uid, text)?;
+        relay_chat(&mut self.core, &self.players, line.clone());
+        Some(line)
+    }
+
+    /// The name of the player or spectator at `addr`.
+    pub fn name_at(&self, addr: &std::net::SocketAddr) -> Option<&str> {
+        self.players.name_at(addr)
     }
 
     pub fn servitors(&mut self) -> (MasterLocalUpdatesListener<'_>, &dyn game::session::Namer) {
@@ -41,81 +159,225 @@ impl Server {
     }
 
     pub fn on_request_to_join(&mut self, request: RequestFromSlave, addr: std::net::SocketAddr) {
-        let RequestFromSlave { name } = request;
-        if let Some(player_uid) = self.players.try_add(name, addr) {
-            let reply = create_update_message(&self.core, &self.players, self.core.server.all());
-            self.core
-                .messenger
-                .send_updates_from_master(&mut self.core.messenger_buffer, addr, player_uid, reply)
-                .unwrap();
+        let RequestFromSlave { name, role, token } = request;
+        // a player who asks again under the same name, because the reply got lost, keeps their
+        // place
+        let token = token.or_else(|| self.players.token_at(&addr, &name));
+        // a slave which joins again from the same address has started over
+        self.leave_session(&addr);
+        let restarted =
+            self.players.lose(&addr).is_some() || self.players.lose_spectator(&addr).is_some();
+        if restarted {
+            println!("The slave at {addr} started over");
+        }
+        let joined = match role {
+            Role::Player => self
+                .players
+                .try_add(name, addr, token)
+                .map(|(uid, token)| (Some(uid), Some(token))),
+            Role::Spectator => self
+                .players
+                .try_add_spectator(name, addr)
+                .map(|()| (None, None)),
+        };
+        match joined {
+            Ok((player_uid, token)) => {
+                let team = self.players.shown_team(player_uid);
+                if let Some(uid) = player_uid {
+                    self.core.session_mut(team).join(uid);
+                }
+                let name = self.players.name_at(&addr).unwrap_or_default();
+                match player_uid {
+                    Some(_) if self.core.sessions.len() > 1 => {
+                        println!("{name} joined {team} from {addr}")
+                    }
+                    Some(_) => println!("{name} joined from {addr}"),
+                    None => println!("{name} is spectating from {addr}"),
+                }
+                let cells = self.core.session(team).all();
+                let reply = create_update_message(&self.core, &self.players, team, cells);
+                self.core
+                    .messenger
+                    .welcome(
+                        &mut self.core.messenger_buffer,
+                        addr,
+                        player_uid,
+                        token,
+                        reply,
+                    )
+                    .unwrap();
+                self.core.messenger.watch(addr);
+                self.announce_players(Some(addr));
+            }
+            Err(rejection) => {
+                println!("Rejected {addr}: {rejection}");
+                self.core
+                    .messenger
+                    .reject(&mut self.core.messenger_buffer, addr, rejection)
+                    .unwrap();
+                if restarted {
+                    self.announce_players(None);
+                }
+            }
         }
     }
 
+    pub fn on_slave_lost(&mut self, addr: std::net::SocketAddr) {
+        self.leave_session(&addr);
+        if let Some(name) = self.players.lose(&addr) {
+            println!("Lost the connection with {name} at {addr}");
+            self.announce_players(None);
+        } else if let Some(name) = self.players.lose_spectator(&addr) {
+            println!("Lost the connection with spectator {name} at {addr}");
+            self.announce_players(None);
+        }
+    }
+
+    /// Takes the player at `addr` off the roster of their session, before they are lost.
+    fn leave_session(&mut self, addr: &std::net::SocketAddr) {
+        if let Some(uid) = self.players.get_uid(addr) {
+            let team = self.players.team_of(uid);
+            self.core.session_mut(team).leave(uid);
+        }
+    }
+
+    /// Lets the players and spectators, except for `except`, know who takes part.
+    fn announce_players(&mut self, except: Option<std::net::SocketAddr>) {
+        broadcast(
+            &mut self.core,
+            &self.players,
+            Team(0),
+            CellUpdates::none(),
+            except,
+        );
+    }
+
+    /// Plays the action on the minefield of the team of the player at `addr`, and returns that
+    /// team together with the updates.
     pub fn on_action_from_slave(
         &mut self,
         action: ActionFromSlave,
         addr: std::net::SocketAddr,
-    ) -> Option<Updates> {
+    ) -> Option<(Team, Updates)> {
         let ActionFromSlave {
             session,
             coord,
-            left,
+            action,
         } = action;
-        if session != self.core.sessionid {
+        if session != self.core.sessionid || self.core.ended() {
+            return None;
+        }
+        let coords = self.coords();
+        let on_field = coords
+            .to_index(&coord)
+            .is_some_and(|index| coords.is_active(index));
+        if !on_field {
+            println!("Ignored an action from {addr} at {coord:?}, which is not on the field");
             return None;
         }
         let uid = self.players.get_uid(&addr)?;
+        let team = self.players.team_of(uid);
         let mut player_listener = MasterLocalUpdatesListener {
             core: &mut self.core,
             players: &self.players,
         };
-        Some(if left {
-            player_listener.on_left_click_id(uid, &coord)
-        } else {
-            player_listener.on_right_click_id(uid, &coord)
+        let updates = match action {
+            Action::Uncover => player_listener.on_left_click_id(uid, &coord),
+            Action::ToggleMark => player_listener.on_right_click_id(uid, &coord),
+            Action::Chord => player_listener.on_chord_id(uid, &coord),
+        };
+        Some((team, updates))
+    }
+}
+
+/// Sends an update to every connected player and spectator, except for `except`. Only those who
+/// watch the minefield of `team` get to see the `cells` that changed on it.
+fn broadcast(
+    core: &mut Core,
+    players: &players::Players,
+    team: Team,
+    cells: CellUpdates,
+    except: Option<std::net::SocketAddr>,
+) {
+    let updates: Vec<UpdateFromMaster> = (0..core.sessions.len() as u8)
+        .map(Team)
+        .map(|shown| {
+            let cells = match shown == team {
+                true => cells.clone(),
+                false => CellUpdates::none(),
+            };
+            create_update_message(core, players, shown, cells)
         })
+        .collect();
+    for (addr, uid) in players.recipients() {
+        if Some(addr) == except {
+            continue;
+        }
+        let Team(shown) = players.shown_team(uid);
+        let update = updates[usize::from(shown)].clone();
+        core.messenger
+            .send_updates_from_master(&mut core.messenger_buffer, addr, uid, update)
+            .unwrap();
+    }
+}
+
+/// Remembers what a player said, and lets everyone know.
+fn relay_chat(core: &mut Core, players: &players::Players, line: chat::ChatLine) {
+    core.chat.push(line.clone());
+    for (addr, _uid) in players.recipients() {
+        core.messenger
+            .send_chat_from_master(&mut core.messenger_buffer, addr, line.clone())
+            .unwrap();
     }
 }
 
+/// An update for those who watch the minefield of `team`.
 fn create_update_message(
     core: &Core,
     players: &players::Players,
+    team: Team,
     cells: CellUpdates,
 ) -> UpdateFromMaster {
+    let session = core.session(team);
     let updates = Updates {
         cells,
-        stats: core.server.stats(),
+        stats: session.stats(),
     };
     let client = UpdateFromMasterForClient {
-        coords: core.server.coords(),
+        coords: session.coords(),
         updates,
     };
     let game = GameUpdateFromMasterForSlave {
-        coords: core.server.coords(),
+        coords: session.coords(),
         session: core.sessionid,
-        status: core.server.status(),
-        stats: core.server.user_stats(),
+        status: core.status(team),
+        stats: core.user_stats(),
+        standings: core.standings(players, team),
     };
     let namer = UpdateFromMasterForNamer {
         names: players.names(),
+        spectators: players.spectator_names(),
     };
     let slave = UpdateFromMasterForSlave { game, namer };
     UpdateFromMaster { client, slave }
 }
 
 struct Core {
-    server: game::server::session::Session,
-    my_uid: SessionUserID,
+    /// A session for every team.
+    sessions: Vec<game::server::session::Session>,
+    /// The player at the master, if any.
+    my_uid: Option<SessionUserID>,
     messenger: std::sync::Arc<Messenger>,
     messenger_buffer: MessengerBuffer,
     sessionid: SessionID,
+    chat: chat::ChatHistory,
 }
 
 impl Core {
     fn new(
-        server: game::server::session::Session,
+        sessions: Vec<game::server::session::Session>,
         messenger: std::sync::Arc<Messenger>,
-        my_uid: SessionUserID,
+        my_uid: Option<SessionUserID>,
     ) -> Self {
         let messenger_buffer = MessengerBuffer::new();
         let sessionid = {
@@ -125,13 +387,105 @@ impl Core {
             SessionID::new(id)
         };
         Self {
-            server,
+            sessions,
             my_uid,
             messenger,
             messenger_buffer,
             sessionid,
+            chat: Default::default(),
         }
     }
+
+    /// Replaces the sessions, under a new session id so that actions meant for the round before
+    /// are ignored.
+    fn start_round(&mut self, sessions: Vec<game::server::session::Session>) {
+        self.sessions = sessions;
+        self.sessionid = SessionID::new(self.sessionid.value().wrapping_add(1));
+    }
+
+    fn session(&self, team: Team) -> &game::server::session::Session {
+        let Team(team) = team;
+        &self.sessions[usize::from(team)]
+    }
+
+    fn session_mut(&mut self, team: Team) -> &mut game::server::session::Session {
+        let Team(team) = team;
+        &mut self.sessions[usize::from(team)]
+    }
+
+    fn teams(&self) -> impl Iterator<Item = (Team, &game::server::session::Session)> + Clone {
+        self.sessions
+            .iter()
+            .enumerate()
+            .map(|(team, session)| (Team(team as u8), session))
+    }
+
+    /// It's over for everyone once a team clears its minefield or runs out of lives.
+    fn ended(&self) -> bool {
+        self.sessions.iter().any(|session| session.status().done())
+    }
+
+    /// The team which cleared its minefield, or else the one which uncovered the most while it
+    /// still had lives left.
+    fn winner(&self) -> Option<Team> {
+        let cleared = self
+            .teams()
+            .find(|(_, session)| matches!(session.status(), Status::Ended { success: true }));
+        let furthest = || {
+            self.teams()
+                .filter(|(_, session)| !session.status().done())
+                .max_by_key(|(_, session)| session.cleared())
+        };
+        cleared.or_else(furthest).map(|(team, _)| team)
+    }
+
+    fn status(&self, team: Team) -> Status {
+        match self.ended() {
+            false => Status::Playing,
+            true => Status::Ended {
+                success: self.winner() == Some(team),
+            },
+        }
+    }
+
+    /// The stats of the players of every team.
+    fn user_stats(&self) -> UserStats {
+        let stats = self
+            .sessions
+            .iter()
+            .flat_map(|session| {
+                let UserStats(stats) = session.user_stats();
+                stats
+            })
+            .collect();
+        UserStats(stats)
+    }
+
+    /// None when there is only a single team.
+    fn standings(&self, players: &players::Players, shown: Team) -> Option<Standings> {
+        if self.sessions.len() < 2 {
+            return None;
+        }
+        let teams = self
+            .teams()
+            .map(|(team, session)| {
+                let (uncovered, safe) = session.cleared();
+                TeamProgress {
+                    team,
+                    players: players.members(team),
+                    uncovered,
+                    safe,
+                    lives_left: session.stats().lives_left,
+                }
+            })
+            .collect();
+        let winner = self.ended().then(|| self.winner()).flatten();
+        Some(Standings {
+            shown,
+            teams,
+            winner,
+        })
+    }
 }
 
 pub struct MasterLocalUpdatesListener<'a> {
@@ -141,44 +495,64 @@ pub struct MasterLocalUpdatesListener<'a> {
 
 impl MasterLocalUpdatesListener<'_> {
     fn on_left_click_id(&mut self, uid: SessionUserID, coord: &Coord) -> Updates {
-        let updates = self.core.server.uncover(coord, uid);
-        self.on_click(updates)
+        let team = self.players.team_of(uid);
+        let updates = self.core.session_mut(team).uncover(coord, uid);
+        self.on_click(team, updates)
     }
 
     fn on_right_click_id(&mut self, uid: SessionUserID, coord: &Coord) -> Updates {
-        let updates = self.core.server.toggle_mark(coord, uid);
-        self.on_click(updates)
+        let team = self.players.team_of(uid);
+        let updates = self.core.session_mut(team).toggle_mark(coord, uid);
+        self.on_click(team, updates)
+    }
+
+    fn on_chord_id(&mut self, uid: SessionUserID, coord: &Coord) -> Updates {
+        let team = self.players.team_of(uid);
+        let updates = self.core.session_mut(team).chord(coord, uid);
+        self.on_click(team, updates)
     }
 
-    fn on_click(&mut self, cells: CellUpdates) -> Updates {
-        let stats = self.core.server.stats();
+    fn on_click(&mut self, team: Team, cells: CellUpdates) -> Updates {
+        let stats = self.core.session(team).stats();
         let updates = Updates {
             cells: cells.clone(),
             stats,
         };
-        let update = create_update_message(self.core, self.players, cells);
-        for peer in self.players.peers() {
-            self.core
-                .messenger
-                .send_updates_from_master(
-                    &mut self.core.messenger_buffer,
-                    peer.addr,
-                    peer.uid,
-                    update.clone(),
-                )
-                .unwrap();
-        }
+        broadcast(self.core, self.players, team, cells, None);
         updates
     }
 }
 
 impl MPLocalPlayerListener for MasterLocalUpdatesListener<'_> {
     fn on_left_click(&mut self, coord: &Coord) -> Option<Updates> {
-        Some(self.on_left_click_id(self.core.my_uid, coord))
+        let uid = self.core.my_uid?;
+        Some(self.on_left_click_id(uid, coord))
     }
 
     fn on_right_click(&mut self, coord: &Coord) -> Option<Updates> {
-        Some(self.on_right_click_id(self.core.my_uid, coord))
+        let uid = self.core.my_uid?;
+        Some(self.on_right_click_id(uid, coord))
+    }
+
+    fn on_chord(&mut self, coord: &Coord) -> Option<Updates> {
+        let uid = self.core.my_uid?;
+        Some(self.on_chord_id(uid, coord))
+    }
+
+    fn on_undo(&mut self) -> Option<Updates> {
+        let team = self.players.my_team()?;
+        let updates = self.core.session_mut(team).undo();
+        Some(self.on_click(team, updates))
+    }
+
+    fn on_chat(&mut self, text: &str) {
+        let line = self
+            .core
+            .my_uid
+            .and_then(|uid| chat::ChatLine::new(uid, text));
+        if let Some(line) = line {
+            relay_chat(self.core, self.players, line);
+        }
     }
 }
 
@@ -191,22 +565,43 @@ mod players {
         pub uid: SessionUserID,
         pub name: String,
         pub addr: std::net::SocketAddr,
+        pub connection: Connection,
+        pub token: RejoinToken,
+        pub team: Team,
+    }
+
+    #[derive(Debug)]
+    pub struct Spectator {
+        pub name: String,
+        pub addr: std::net::SocketAddr,
     }
 
     #[derive(Debug)]
     pub struct Players {
-        my_uid: SessionUserID,
-        my_name: String,
+        /// The uid and name of the player at the master, if any.
+        /// The player at the master always plays in the first team.
+        me: Option<(SessionUserID, String)>,
         slaves: Vec<Player>,
+        spectators: Vec<Spectator>,
+        teams: u8,
     }
 
     impl Players {
-        pub fn new(my_uid: SessionUserID, my_name: String) -> Self {
+        pub fn new(my_uid: SessionUserID, my_name: String, teams: u8) -> Self {
+            Self {
+                me: Some((my_uid, my_name)),
+                ..Self::headless(teams)
+            }
+        }
+
+        pub fn headless(teams: u8) -> Self {
             let slaves = Vec::new();
+            let spectators = Vec::new();
             Self {
-                my_uid,
-                my_name,
+                me: None,
                 slaves,
+                spectators,
+                teams: teams.max(1),
             }
         }
 
@@ -214,76 +609,251 @@ mod players {
             &mut self,
             name: String,
             addr: std::net::SocketAddr,
-        ) -> Option<SessionUserID> {
-            let count = 1 + self.slaves.len();
-            let space_available = count < MAX_PLAYERS.into();
-            let addr_in_use = self.slaves.iter().any(|player| player.addr == addr);
-            (space_available && !addr_in_use).then(|| {
-                let uid = next_uid(&self.slaves);
-                let player = Player { uid, name, addr };
-                self.slaves.push(player);
-                uid
-            })
+            token: Option<RejoinToken>,
+        ) -> Result<(SessionUserID, RejoinToken), Rejection> {
+            let known =
+                token.and_then(|token| self.slaves.iter_mut().find(|player| player.token == token));
+            if let Some(player) = known {
+                // takes back their place, under their old name
+                player.addr = addr;
+                player.connection = Connection::Connected;
+                return Ok((player.uid, player.token));
+            }
+            // players who lost their connection keep their place, as their moves stay theirs
+            let count = usize::from(self.me.is_some()) + self.slaves.len();
+            if count >= MAX_PLAYERS.into() {
+                return Err(Rejection::GameFull);
+            }
+            if self.name_taken(&name) {
+                return Err(Rejection::NameTaken);
+            }
+            let uid = self.next_uid();
+            let team = self.smallest_team();
+            let token = RejoinToken(rand::random());
+            let player = Player {
+                uid,
+                name,
+                addr,
+                connection: Connection::Connected,
+                token,
+                team,
+            };
+            self.slaves.push(player);
+            Ok((uid, token))
+        }
+
+        /// Spectators don't take up a place, so only their name can be taken.
+        pub fn try_add_spectator(
+            &mut self,
+            name: String,
+            addr: std::net::SocketAddr,
+        ) -> Result<(), Rejection> {
+            if self.name_taken(&name) {
+                return Err(Rejection::NameTaken);
+            }
+            self.spectators.push(Spectator { name, addr });
+            Ok(())
+        }
+
+        fn name_taken(&self, name: &str) -> bool {
+            self.me.as_ref().is_some_and(|(_, my_name)| my_name == name)
+                // the name of a player who lost their connection waits for them to come back
+                || self.slaves.iter().any(|player| player.name == name)
+                || self
+                    .spectators
+                    .iter()
+                    .any(|spectator| spectator.name == name)
+        }
+
+        /// The lowest uid which isn't given out yet.
+        fn next_uid(&self) -> SessionUserID {
+            (1..=u8::MAX)
+                .map(SessionUserID)
+                .find(|&uid| {
+                    self.me.as_ref().map(|&(my_uid, _)| my_uid) != Some(uid)
+                        && self.slaves.iter().all(|player| player.uid != uid)
+                })
+                .expect("Master ran out of userids")
+        }
+
+        /// The team with the fewest connected players, where a new player goes.
+        fn smallest_team(&self) -> Team {
+            (0..self.teams)
+                .map(Team)
+                .min_by_key(|&team| {
+                    let me = self.my_team() == Some(team);
+                    usize::from(me) + self.connected().filter(|p| p.team == team).count()
+                })
+                .expect("There is always a team")
+        }
+
+        pub fn my_team(&self) -> Option<Team> {
+            self.me.as_ref().map(|_| Team(0))
+        }
+
+        pub fn team_of(&self, uid: SessionUserID) -> Team {
+            if self.me.as_ref().is_some_and(|&(my_uid, _)| my_uid == uid) {
+                return Team(0);
+            }
+            self.slaves
+                .iter()
+                .find_map(|player| (player.uid == uid).then_some(player.team))
+                .expect("Master can't find that userid")
+        }
+
+        /// The team of which the minefield is shown to a player, spectators watch the first team.
+        pub fn shown_team(&self, uid: Option<SessionUserID>) -> Team {
+            uid.map_or(Team(0), |uid| self.team_of(uid))
+        }
+
+        /// Everyone who plays in `team`, including those who lost their connection.
+        pub fn members(&self, team: Team) -> Vec<SessionUserID> {
+            let me = self
+                .me
+                .iter()
+                .filter(|_| team == Team(0))
+                .map(|&(uid, _)| uid);
+            let slaves = self
+                .slaves
+                .iter()
+                .filter(|player| player.team == team)
+                .map(|player| player.uid);
+            me.chain(slaves).collect()
+        }
+
+        /// Removes the spectator at `addr`, and returns their name.
+        pub fn lose_spectator(&mut self, addr: &std::net::SocketAddr) -> Option<String> {
+            let index = self
+                .spectators
+                .iter()
+                .position(|spectator| spectator.addr == *addr)?;
+            Some(self.spectators.remove(index).name)
+        }
+
+        /// Forgets the players who lost their connection, which frees up their places and names.
+        pub fn forget_lost(&mut self) {
+            self.slaves
+                .retain(|player| player.connection == Connection::Connected);
+        }
+
+        /// Marks the player at `addr` as gone, and returns their name.
+        pub fn lose(&mut self, addr: &std::net::SocketAddr) -> Option<&str> {
+            let player = self.slaves.iter_mut().find(|player| {
+                player.connection == Connection::Connected && player.addr == *addr
+            })?;
+            player.connection = Connection::Lost;
+            Some(&player.name)
+        }
+
+        fn connected(&self) -> impl Iterator<Item = &Player> {
+            self.slaves
+                .iter()
+                .filter(|player| player.connection == Connection::Connected)
         }
 
         pub fn names(&self) -> UserNames {
             UserNames(
-                std::iter::once(UserName {
-                    uid: self.my_uid,
-                    name: self.my_name.clone(),
-                })
-                .chain(
-                    self.slaves
-                        .iter()
-                        .map(|Player { uid, name, addr: _ }| UserName {
+                self.me
+                    .iter()
+                    .map(|(uid, name)| UserName {
+                        uid: *uid,
+                        name: name.clone(),
+                        connection: Connection::Connected,
+                    })
+                    .chain(self.slaves.iter().map(
+                        |Player {
+                             uid,
+                             name,
+                             addr: _,
+                             connection,
+                             token: _,
+                             team: _,
+                         }| UserName {
                             uid: *uid,
                             name: name.clone(),
-                        }),
-                )
-                .collect(),
+                            connection: *connection,
+                        },
+                    ))
+                    .collect(),
             )
         }
 
+        /// The token of the connected player at `peer`, if they go by `name`.
+        pub fn token_at(&self, peer: &std::net::SocketAddr, name: &str) -> Option<RejoinToken> {
+            self.connected()
+                .find(|player| player.addr == *peer && player.name == name)
+                .map(|player| player.token)
+        }
+
+        /// Only finds players who are connected.
         pub fn get_uid(&self, peer: &std::net::SocketAddr) -> Option<SessionUserID> {
-            self.slaves
-                .iter()
-                .find_map(|Player { uid, name: _, addr }| (addr == peer).then_some(uid))
+            self.connected()
+                .find_map(|Player { uid, addr, .. }| (addr == peer).then_some(uid))
                 .copied()
         }
 
-        pub fn peers(&self) -> impl Iterator<Item = &Player> {
-            self.slaves.iter()
+        /// Finds connected players and spectators.
+        pub fn name_at(&self, peer: &std::net::SocketAddr) -> Option<&str> {
+            let player = self
+                .connected()
+                .find_map(|Player { name, addr, .. }| (addr == peer).then_some(name));
+            let spectator = || {
+                self.spectators
+                    .iter()
+                    .find_map(|Spectator { name, addr }| (addr == peer).then_some(name))
+            };
+            player.or_else(spectator).map(String::as_str)
+        }
+
+        pub fn spectator_names(&self) -> Vec<String> {
+            self.spectators
+                .iter()
+                .map(|spectator| spectator.name.clone())
+                .collect()
+        }
+
+        /// Where updates go: the players who are connected, followed by the spectators.
+        pub fn recipients(
+            &self,
+        ) -> impl Iterator<Item = (std::net::SocketAddr, Option<SessionUserID>)> + '_ {
+            self.connected()
+                .map(|player| (player.addr, Some(player.uid)))
+                .chain(
+                    self.spectators
+                        .iter()
+                        .map(|spectator| (spectator.addr, None)),
+                )
         }
     }
 
     impl game::session::Namer for Players {
         fn name(&self, uid: SessionUserID) -> &str {
             let Players {
-                my_uid,
-                my_name,
+                me,
                 slaves,
+                spectators: _,
+                teams: _,
             } = self;
-            if *my_uid == uid {
-                return my_name;
+            if let Some((my_uid, my_name)) = me {
+                if *my_uid == uid {
+                    return my_name;
+                }
             }
             let id = uid;
             slaves
                 .iter()
-                .find_map(|Player { uid, name, addr: _ }| (id == *uid).then_some(name))
+                .find_map(|Player { uid, name, .. }| (id == *uid).then_some(name))
                 .expect("Master can't find that userid")
         }
     }
-
-    fn next_uid(slaves: &Vec<Player>) -> SessionUserID {
-        let uid = 2 + slaves.len();
-        SessionUserID(uid.try_into().unwrap())
-    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::game::session::Namer;
+
     fn make_addr(port: u16) -> std::net::SocketAddr {
         std::net::SocketAddr::V4(std::net::SocketAddrV4::new(
             std::net::Ipv4Addr::UNSPECIFIED,
@@ -292,9 +862,9 @@ mod tests {
     }
 
     fn create_players() -> players::Players {
-        let mut p = players::Players::new(SessionUserID(3), "Cedric".into());
-        p.try_add("Alice".into(), make_addr(1)).unwrap();
-        p.try_add("Bob".into(), make_addr(2)).unwrap();
+        let mut p = players::Players::new(SessionUserID(3), "Cedric".into(), 1);
+        p.try_add("Alice".into(), make_addr(1), None).unwrap();
+        p.try_add("Bob".into(), make_addr(2), None).unwrap();
         p
     }
 
@@ -307,4 +877,295 @@ mod tests {
         assert_eq!(names.next().unwrap().name, "Alice");
         assert_eq!(names.next().unwrap().name, "Bob");
     }
+
+    #[test]
+    fn players_reject_taken_name() {
+        let mut players = create_players();
+        let rejection = players.try_add("Cedric".into(), make_addr(3), None);
+        assert_eq!(rejection, Err(Rejection::NameTaken));
+        let rejection = players.try_add("Alice".into(), make_addr(3), None);
+        assert_eq!(rejection, Err(Rejection::NameTaken));
+    }
+
+    #[test]
+    fn players_reject_when_full() {
+        let mut players = create_players();
+        for port in 3..u16::from(MAX_PLAYERS) {
+            players
+                .try_add(format!("Player {port}"), make_addr(port), None)
+                .unwrap();
+        }
+        let rejection = players.try_add("Dave".into(), make_addr(100), None);
+        assert_eq!(rejection, Err(Rejection::GameFull));
+    }
+
+    #[test]
+    fn players_who_are_lost_keep_their_place() {
+        let mut players = create_players();
+        for port in 3..u16::from(MAX_PLAYERS) {
+            players
+                .try_add(format!("Player {port}"), make_addr(port), None)
+                .unwrap();
+        }
+        let alice = players.get_uid(&make_addr(1)).unwrap();
+        assert_eq!(players.lose(&make_addr(1)), Some("Alice"));
+        assert_eq!(players.lose(&make_addr(1)), None);
+        assert_eq!(players.get_uid(&make_addr(1)), None);
+        assert_eq!(players.recipients().count(), usize::from(MAX_PLAYERS) - 2);
+        let rejection = players.try_add("Dave".into(), make_addr(100), None);
+        assert_eq!(rejection, Err(Rejection::GameFull));
+        assert_eq!(players.name(alice), "Alice");
+    }
+
+    #[test]
+    fn players_show_who_is_lost() {
+        let mut players = create_players();
+        players.lose(&make_addr(2));
+        let connections: Vec<_> = players
+            .names()
+            .players()
+            .into_iter()
+            .map(|(_uid, connection)| connection)
+            .collect();
+        assert_eq!(
+            connections,
+            vec![
+                Connection::Connected,
+                Connection::Connected,
+                Connection::Lost
+            ]
+        );
+    }
+
+    #[test]
+    fn players_who_rejoin_get_their_place_back() {
+        let mut players = create_players();
+        let (dave, token) = players.try_add("Dave".into(), make_addr(3), None).unwrap();
+        players.lose(&make_addr(3));
+        let rejoined = players.try_add("Eve".into(), make_addr(4), Some(token));
+        assert_eq!(rejoined, Ok((dave, token)));
+        assert_eq!(players.name(dave), "Dave");
+        assert_eq!(players.get_uid(&make_addr(4)), Some(dave));
+        let RejoinToken(unknown) = token;
+        let (eve, _) = players
+            .try_add("Eve".into(), make_addr(5), Some(RejoinToken(unknown + 1)))
+            .unwrap();
+        assert_ne!(eve, dave);
+    }
+
+    #[test]
+    fn players_who_rejoin_after_a_newcomer_get_their_place_back() {
+        let mut players = create_players();
+        let (dave, token) = players.try_add("Dave".into(), make_addr(3), None).unwrap();
+        players.lose(&make_addr(3));
+        let rejection = players.try_add("Dave".into(), make_addr(4), None);
+        assert_eq!(rejection, Err(Rejection::NameTaken));
+        for port in 4..u16::from(MAX_PLAYERS) {
+            players
+                .try_add(format!("Player {port}"), make_addr(port), None)
+                .unwrap();
+        }
+        let rejection = players.try_add("Eve".into(), make_addr(100), None);
+        assert_eq!(rejection, Err(Rejection::GameFull));
+        let rejoined = players.try_add("Dave".into(), make_addr(101), Some(token));
+        assert_eq!(rejoined, Ok((dave, token)));
+        assert_eq!(players.name(dave), "Dave");
+        assert_eq!(players.team_of(dave), Team(0));
+        assert_eq!(players.get_uid(&make_addr(101)), Some(dave));
+    }
+
+    #[test]
+    fn players_who_ask_again_keep_their_place() {
+        let mut players = create_players();
+        let (dave, token) = players.try_add("Dave".into(), make_addr(3), None).unwrap();
+        assert_eq!(players.token_at(&make_addr(3), "Eve"), None);
+        assert_eq!(players.token_at(&make_addr(3), "Dave"), Some(token));
+        players.lose(&make_addr(3));
+        let again = players.try_add("Dave".into(), make_addr(3), Some(token));
+        assert_eq!(again, Ok((dave, token)));
+        assert_eq!(players.names().players().len(), 4);
+    }
+
+    #[test]
+    fn spectators_watch_without_taking_a_place() {
+        let mut players = create_players();
+        players
+            .try_add_spectator("Dave".into(), make_addr(100))
+            .unwrap();
+        let rejection = players.try_add("Dave".into(), make_addr(101), None);
+        assert_eq!(rejection, Err(Rejection::NameTaken));
+        let rejection = players.try_add_spectator("Alice".into(), make_addr(101));
+        assert_eq!(rejection, Err(Rejection::NameTaken));
+        for port in 3..u16::from(MAX_PLAYERS) {
+            players
+                .try_add(format!("Player {port}"), make_addr(port), None)
+                .unwrap();
+        }
+        players
+            .try_add_spectator("Eve".into(), make_addr(101))
+            .unwrap();
+        assert_eq!(players.get_uid(&make_addr(100)), None);
+        assert!(players.recipients().any(|r| r == (make_addr(100), None)));
+        assert_eq!(players.lose_spectator(&make_addr(100)), Some("Dave".into()));
+        assert_eq!(players.spectator_names(), vec!["Eve".to_string()]);
+    }
+
+    #[test]
+    fn headless_players_leave_no_place_for_the_master() {
+        let mut players = players::Players::headless(1);
+        let (alice, _) = players.try_add("Alice".into(), make_addr(1), None).unwrap();
+        assert_eq!(alice, SessionUserID(1));
+        assert_eq!(
+            players.names().players(),
+            vec![(alice, Connection::Connected)]
+        );
+        for port in 2..=u16::from(MAX_PLAYERS) {
+            players
+                .try_add(format!("Player {port}"), make_addr(port), None)
+                .unwrap();
+        }
+        let rejection = players.try_add("Dave".into(), make_addr(100), None);
+        assert_eq!(rejection, Err(Rejection::GameFull));
+    }
+
+    #[test]
+    fn players_are_spread_over_the_teams() {
+        let mut players = players::Players::new(SessionUserID(1), "Cedric".into(), 2);
+        let (alice, _) = players.try_add("Alice".into(), make_addr(1), None).unwrap();
+        let (bob, _) = players.try_add("Bob".into(), make_addr(2), None).unwrap();
+        assert_eq!(players.my_team(), Some(Team(0)));
+        assert_eq!(players.team_of(alice), Team(1));
+        assert_eq!(players.team_of(bob), Team(0));
+        assert_eq!(players.members(Team(0)), vec![SessionUserID(1), bob]);
+        players.lose(&make_addr(1));
+        let (dave, _) = players.try_add("Dave".into(), make_addr(3), None).unwrap();
+        assert_eq!(players.team_of(dave), Team(1));
+        assert_eq!(players.members(Team(1)), vec![alice, dave]);
+        assert_eq!(players.shown_team(None), Team(0));
+    }
+
+    #[test]
+    fn slaves_play_on_in_the_next_round() {
+        const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+        let master_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 47820));
+        let slave_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
+        let messenger = std::sync::Arc::new(Messenger::new(
+            Transport::Udp.listen(master_addr),
+            Codec::Binary,
+            TIMEOUT,
+        ));
+        let config = game::session::SessionConfig {
+            coords: Coordinations::from_width_and_height(8, 10),
+            mines: Mines(14),
+            lives: Lives(3),
+            lives_policy: LivesPolicy::Shared,
+            seed: None,
+            first_click: FirstClick::Safe,
+            generator: Generator::Random,
+            undo: false,
+        };
+        let mut server = Server::headless(team_sessions(&config, 1), messenger.clone());
+        let slave = std::sync::Arc::new(Messenger::new(
+            Transport::Udp.connect(slave_addr, master_addr),
+            Codec::Binary,
+            TIMEOUT,
+        ));
+        let joining = {
+            let slave = slave.clone();
+            std::thread::spawn(move || {
+                let mut buffer = MessengerBuffer::new();
+                slave.request_to_join(&mut buffer, "Alice", Role::Player, None)
+            })
+        };
+        let start = std::time::Instant::now();
+        let mut buffer = MessengerBuffer::new();
+        while !joining.is_finished() {
+            assert!(start.elapsed() < TIMEOUT, "Slave didn't join");
+            if let Some((MessageFromSlave::Join(request), addr)) =
+                messenger.receive_packet_from_slave(&mut buffer)
+            {
+                server.on_request_to_join(request, addr);
+            }
+        }
+        let (welcome, _) = joining.join().unwrap().unwrap();
+        let first_round = welcome.slave.game.session;
+        server.start_round(team_sessions(&config, 1));
+        let mut slave_buffer = MessengerBuffer::new();
+        let second_round = loop {
+            assert!(start.elapsed() < TIMEOUT, "Slave didn't get the next round");
+            if let Some(MessageFromMaster::Update(update)) =
+                slave.receive_packet_from_master(&mut slave_buffer)
+            {
+                if update.slave.game.session != first_round {
+                    break update.slave.game.session;
+                }
+            }
+            let _ = messenger.receive_packet_from_slave(&mut buffer);
+            messenger.retransmit();
+        };
+        slave.left_click(&mut slave_buffer, second_round, &Coord { x: 1, y: 1 });
+        let (action, addr) = loop {
+            assert!(start.elapsed() < TIMEOUT, "Master didn't receive the click");
+            if let Some((MessageFromSlave::Action(action), addr)) =
+                messenger.receive_packet_from_slave(&mut buffer)
+            {
+                break (action, addr);
+            }
+            let _ = slave.receive_packet_from_master(&mut slave_buffer);
+        };
+        assert!(server.on_action_from_slave(action, addr).is_some());
+    }
+
+    /// Lets every team start somewhere else, then uncovers all cells and compares the results.
+    fn assert_teams_play_the_same_minefield(first_click: FirstClick, generator: Generator) {
+        let config = game::session::SessionConfig {
+            coords: Coordinations::from_width_and_height(8, 10),
+            mines: Mines(14),
+            lives: Lives(100),
+            lives_policy: LivesPolicy::Shared,
+            seed: None,
+            first_click,
+            generator,
+            undo: false,
+        };
+        let mut sessions = team_sessions(&config, 2);
+        assert_eq!(sessions.len(), 2);
+        let uid = SessionUserID(1);
+        let _ = sessions[0].uncover(&Coord { x: 1, y: 1 }, uid);
+        let _ = sessions[1].uncover(&Coord { x: 6, y: 8 }, uid);
+        for (x, y) in itertools::iproduct!(0..8, 0..10) {
+            let coord = Coord { x, y };
+            let _ = sessions[0].uncover(&coord, uid);
+            let _ = sessions[1].uncover(&coord, uid);
+        }
+        let cells = |session: &game::server::session::Session| -> Vec<Cell> {
+            let CellUpdates(updates) = session.all();
+            updates.into_iter().map(|update| update.cell).collect()
+        };
+        assert_eq!(cells(&sessions[0]), cells(&sessions[1]));
+    }
+
+    #[test]
+    fn teams_play_the_same_minefield() {
+        assert_teams_play_the_same_minefield(FirstClick::Unprotected, Generator::Random);
+    }
+
+    #[test]
+    fn teams_play_the_same_minefield_with_a_safe_first_click() {
+        assert_teams_play_the_same_minefield(FirstClick::Safe, Generator::Random);
+    }
+
+    #[test]
+    fn teams_play_the_same_minefield_with_an_opening_first_click() {
+        assert_teams_play_the_same_minefield(FirstClick::Opening, Generator::Random);
+    }
+
+    #[test]
+    fn teams_play_the_same_minefield_without_guessing() {
+        let budget = NoGuessBudget {
+            attempts: 100,
+            time: std::time::Duration::ZERO,
+        };
+        assert_teams_play_the_same_minefield(FirstClick::Opening, Generator::NoGuess(budget));
+    }
 }
@@ -35,6 +35,10 @@ impl game::session::Session for Session {
         let user_stats = server.user_stats();
         let status = server.status();
         let coords = server.coords();
+        let players = server.players();
+        let spectators = server.spectators();
+        let teams = server.standings();
+        let chat = Some(server.chat());
         let stats = client.stats();
         let field_provider: &game::client::session::Session = &client;
         let (mut local_player_listener, namer) = server.servitors();
@@ -44,6 +48,11 @@ impl game::session::Session for Session {
             user_stats,
             status,
             coords,
+            players,
+            spectators,
+            teams,
+            chat,
+            connection: Connection::Connected,
             stats,
             namer,
             field_provider,
@@ -90,6 +99,20 @@ mod record_updates {
             let updates = self.local_player_listener.on_right_click(coord);
             aggregate(&mut self.record, updates)
         }
+
+        fn on_chord(&mut self, coord: &Coord) {
+            let updates = self.local_player_listener.on_chord(coord);
+            aggregate(&mut self.record, updates)
+        }
+
+        fn on_undo(&mut self) {
+            let updates = self.local_player_listener.on_undo();
+            aggregate(&mut self.record, updates)
+        }
+
+        fn on_chat(&mut self, text: &str) {
+            self.local_player_listener.on_chat(text)
+        }
     }
 
     fn aggregate(record: &mut Option<Updates>, new: Option<Updates>) {
@@ -3,9 +3,14 @@ use super::*;
 pub trait SlaveListener {
     fn on_request_to_join(&mut self, request: RequestFromSlave, addr: std::net::SocketAddr);
     fn on_action_from_slave(&mut self, action: ActionFromSlave, addr: std::net::SocketAddr);
+    fn on_chat_from_slave(&mut self, text: String, addr: std::net::SocketAddr);
+    fn on_slave_lost(&mut self, addr: std::net::SocketAddr);
 }
 
 pub trait MPLocalPlayerListener {
     fn on_left_click(&mut self, coord: &Coord) -> Option<Updates>;
     fn on_right_click(&mut self, coord: &Coord) -> Option<Updates>;
+    fn on_chord(&mut self, coord: &Coord) -> Option<Updates>;
+    fn on_undo(&mut self) -> Option<Updates>;
+    fn on_chat(&mut self, text: &str);
 }
@@ -0,0 +1,210 @@
+//! How messages are laid out on the wire.
+//! Every datagram starts with a header, which tells whether it is an acknowledgement, a message or
+//! a heartbeat. Acknowledgements and messages carry the stream and the sequence number. A message is
+//! followed by the codec of its payload and the payload itself, so a receiver can always decode it,
+//! whatever codec the sender prefers. A message which doesn't fit in a single datagram is sent in
+//! parts, which follow each other in sequence, and of which only the last one says it is a message.
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// The encoding of messages.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Codec {
+    #[default]
+    Binary,
+    /// Readable, for debugging.
+    Json,
+}
+
+const ACK: u8 = 0;
+const MESSAGE: u8 = 1;
+const HEARTBEAT: u8 = 2;
+const PART: u8 = 3;
+const BINARY: u8 = 0;
+const JSON: u8 = 1;
+const STREAM_SIZE: usize = std::mem::size_of::<u32>();
+const SEQ_SIZE: usize = std::mem::size_of::<u64>();
+/// The size of what comes before the payload of a message.
+pub const MESSAGE_HEADER_SIZE: usize = 1 + STREAM_SIZE + SEQ_SIZE + 1;
+
+impl Codec {
+    pub fn encode<M>(&self, message: &M, buffer: &mut Vec<u8>) -> Result<(), &'static str>
+    where
+        M: Serialize,
+    {
+        match self {
+            Codec::Binary => bincode::serialize_into(buffer, message)
+                .map_err(|_| "Failed to encode binary message"),
+            Codec::Json => {
+                serde_json::to_writer(buffer, message).map_err(|_| "Failed to encode json message")
+            }
+        }
+    }
+
+    pub fn decode<M>(&self, payload: &[u8]) -> Result<M, &'static str>
+    where
+        M: DeserializeOwned,
+    {
+        match self {
+            Codec::Binary => {
+                bincode::deserialize(payload).map_err(|_| "Failed to decode binary message")
+            }
+            Codec::Json => {
+                serde_json::from_slice(payload).map_err(|_| "Failed to decode json message")
+            }
+        }
+    }
+
+    /// Picks the codec which both sides prefer, falling back to json.
+    pub fn negotiate(&self, other: Codec) -> Codec {
+        match (self, other) {
+            (Codec::Binary, Codec::Binary) => Codec::Binary,
+            _ => Codec::Json,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Envelope<'a> {
+    Message {
+        stream: u32,
+        seq: u64,
+        codec: Codec,
+        /// Whether or not the message is complete with this payload, or more parts follow.
+        last: bool,
+        payload: &'a [u8],
+    },
+    Ack {
+        stream: u32,
+        seq: u64,
+    },
+    Heartbeat,
+}
+
+impl<'a> Envelope<'a> {
+    /// Writes the header of a message, or of a part of one when it isn't the `last` part, after
+    /// which the payload can be encoded.
+    pub fn write_message_header(
+        stream: u32,
+        seq: u64,
+        codec: Codec,
+        last: bool,
+        buffer: &mut Vec<u8>,
+    ) {
+        buffer.push(match last {
+            true => MESSAGE,
+            false => PART,
+        });
+        buffer.extend_from_slice(&stream.to_be_bytes());
+        buffer.extend_from_slice(&seq.to_be_bytes());
+        buffer.push(match codec {
+            Codec::Binary => BINARY,
+            Codec::Json => JSON,
+        });
+    }
+
+    pub fn write_ack(stream: u32, seq: u64, buffer: &mut Vec<u8>) {
+        buffer.push(ACK);
+        buffer.extend_from_slice(&stream.to_be_bytes());
+        buffer.extend_from_slice(&seq.to_be_bytes());
+    }
+
+    pub fn write_heartbeat(buffer: &mut Vec<u8>) {
+        buffer.push(HEARTBEAT);
+    }
+
+    pub fn read(datagram: &'a [u8]) -> Result<Self, &'static str> {
+        let (&kind, rest) = datagram.split_first().ok_or("Empty datagram")?;
+        if kind == HEARTBEAT {
+            return Ok(Envelope::Heartbeat);
+        }
+        let stream = rest.get(..STREAM_SIZE).ok_or("Datagram without stream")?;
+        let stream = u32::from_be_bytes(stream.try_into().unwrap());
+        let rest = &rest[STREAM_SIZE..];
+        let seq = rest
+            .get(..SEQ_SIZE)
+            .ok_or("Datagram without sequence number")?;
+        let seq = u64::from_be_bytes(seq.try_into().unwrap());
+        let rest = &rest[SEQ_SIZE..];
+        match kind {
+            ACK => Ok(Envelope::Ack { stream, seq }),
+            MESSAGE | PART => {
+                let (&codec, payload) = rest.split_first().ok_or("Message without codec")?;
+                let codec = match codec {
+                    BINARY => Codec::Binary,
+                    JSON => Codec::Json,
+                    _ => return Err("Unknown codec"),
+                };
+                Ok(Envelope::Message {
+                    stream,
+                    seq,
+                    codec,
+                    last: kind == MESSAGE,
+                    payload,
+                })
+            }
+            _ => Err("Unknown kind of datagram"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_can_be_read_back() {
+        for (codec, last) in [
+            (Codec::Binary, true),
+            (Codec::Json, true),
+            (Codec::Json, false),
+        ] {
+            let mut buffer = Vec::new();
+            Envelope::write_message_header(3, 7, codec, last, &mut buffer);
+            let header = buffer.len();
+            assert_eq!(header, MESSAGE_HEADER_SIZE);
+            codec.encode(&(3u8, "three"), &mut buffer).unwrap();
+            let envelope = Envelope::read(&buffer).unwrap();
+            let payload = &buffer[header..];
+            assert_eq!(
+                envelope,
+                Envelope::Message {
+                    stream: 3,
+                    seq: 7,
+                    codec,
+                    last,
+                    payload
+                }
+            );
+            let decoded: (u8, String) = codec.decode(payload).unwrap();
+            assert_eq!(decoded, (3, "three".to_string()));
+        }
+    }
+
+    #[test]
+    fn ack_can_be_read_back() {
+        let mut buffer = Vec::new();
+        Envelope::write_ack(u32::MAX, 1 << 40, &mut buffer);
+        assert_eq!(
+            Envelope::read(&buffer),
+            Ok(Envelope::Ack {
+                stream: u32::MAX,
+                seq: 1 << 40
+            })
+        );
+    }
+
+    #[test]
+    fn heartbeat_can_be_read_back() {
+        let mut buffer = Vec::new();
+        Envelope::write_heartbeat(&mut buffer);
+        assert_eq!(Envelope::read(&buffer), Ok(Envelope::Heartbeat));
+    }
+
+    #[test]
+    fn json_is_a_fallback() {
+        assert_eq!(Codec::Binary.negotiate(Codec::Binary), Codec::Binary);
+        assert_eq!(Codec::Binary.negotiate(Codec::Json), Codec::Json);
+        assert_eq!(Codec::Json.negotiate(Codec::Binary), Codec::Json);
+    }
+}
@@ -0,0 +1,99 @@
+//! Tells whether peers are still there.
+//! Every watched peer gets a heartbeat now and then. A peer which hasn't been heard from for
+//! longer than the timeout is lost, and isn't watched anymore.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct Heartbeats {
+    timeout: Duration,
+    last_beat: Option<Instant>,
+    /// When every watched peer was last heard from.
+    heard: HashMap<SocketAddr, Instant>,
+}
+
+impl Heartbeats {
+    pub fn new(timeout: Duration) -> Self {
+        let last_beat = None;
+        let heard = HashMap::new();
+        Self {
+            timeout,
+            last_beat,
+            heard,
+        }
+    }
+
+    pub fn watch(&mut self, addr: SocketAddr, now: Instant) {
+        self.heard.insert(addr, now);
+    }
+
+    pub fn heard(&mut self, addr: SocketAddr, now: Instant) {
+        if let Some(heard) = self.heard.get_mut(&addr) {
+            *heard = now;
+        }
+    }
+
+    /// Returns the peers to send a heartbeat to, when it is time for one.
+    pub fn due(&mut self, now: Instant) -> Vec<SocketAddr> {
+        if let Some(beat) = self.last_beat {
+            if now.saturating_duration_since(beat) < HEARTBEAT_INTERVAL {
+                return Vec::new();
+            }
+        }
+        self.last_beat = Some(now);
+        self.heard.keys().copied().collect()
+    }
+
+    /// Stops watching the peers which have been silent for too long, and returns them.
+    pub fn lost(&mut self, now: Instant) -> Vec<SocketAddr> {
+        let lost: Vec<SocketAddr> = self
+            .heard
+            .iter()
+            .filter(|(_, &heard)| now.saturating_duration_since(heard) > self.timeout)
+            .map(|(&addr, _)| addr)
+            .collect();
+        for addr in &lost {
+            self.heard.remove(addr);
+        }
+        lost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn heartbeats_go_to_watched_peers_once_per_interval() {
+        let mut heartbeats = Heartbeats::new(Duration::from_secs(5));
+        let now = Instant::now();
+        heartbeats.heard(addr(1), now);
+        assert!(heartbeats.due(now).is_empty());
+        heartbeats.watch(addr(1), now);
+        assert!(heartbeats.due(now).is_empty());
+        let later = now + HEARTBEAT_INTERVAL;
+        assert_eq!(heartbeats.due(later), vec![addr(1)]);
+        assert!(heartbeats.due(later).is_empty());
+    }
+
+    #[test]
+    fn silent_peers_are_lost() {
+        let timeout = Duration::from_secs(5);
+        let mut heartbeats = Heartbeats::new(timeout);
+        let now = Instant::now();
+        heartbeats.watch(addr(1), now);
+        heartbeats.watch(addr(2), now);
+        heartbeats.heard(addr(2), now + timeout);
+        assert!(heartbeats.lost(now + timeout).is_empty());
+        assert_eq!(heartbeats.lost(now + timeout * 3 / 2), vec![addr(1)]);
+        assert!(heartbeats.lost(now + timeout * 3 / 2).is_empty());
+        assert_eq!(heartbeats.lost(now + timeout * 3), vec![addr(2)]);
+    }
+}
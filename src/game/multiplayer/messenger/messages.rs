@@ -5,13 +5,18 @@ use serde::{Deserialize, Serialize};
 
 use std::collections::HashMap;
 
+/// The version of the protocol, which masters and slaves must agree upon.
+pub const VERSION: u16 = 12;
+
 pub mod from_slave {
     use super::*;
 
     #[derive(Serialize, Deserialize, Debug)]
     pub enum MessageSentByClient {
+        /// Kept as the first variant, see `Handshake`.
         Join(JoinRequest),
         Click(Click),
+        Chat(Chat),
     }
 
     impl From<MessageSentByClient> for MessageFromSlave {
@@ -19,14 +24,20 @@ pub mod from_slave {
             match msg {
                 MessageSentByClient::Join(j) => Self::Join(j.into()),
                 MessageSentByClient::Click(c) => Self::Action(c.into()),
+                MessageSentByClient::Chat(Chat { text }) => Self::Chat(text),
             }
         }
     }
 
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct Chat {
+        pub text: String,
+    }
+
     #[derive(Serialize, Deserialize, Debug)]
     pub struct Click {
         pub session: data::SID,
-        pub left: bool,
+        pub action: data::Action,
         pub coord: data::Coord,
     }
 
@@ -34,26 +45,48 @@ pub mod from_slave {
         fn from(click: Click) -> Self {
             let Click {
                 session,
-                left,
+                action,
                 coord,
             } = click;
             Self {
                 session: session.into(),
                 coord: coord.into(),
-                left,
+                action: action.into(),
             }
         }
     }
 
+    /// The start of a join request, which every version of the protocol keeps the same.
+    /// This allows a master to tell a slave of another version why it can't join.
+    #[derive(Deserialize, Debug)]
+    pub enum Handshake {
+        Join { version: u16 },
+    }
+
     #[derive(Serialize, Deserialize, Debug)]
     pub struct JoinRequest {
+        /// Kept as the first field, see `Handshake`.
+        pub version: u16,
         pub name: String,
+        /// The codec which the slave prefers for the updates from the master.
+        pub codec: data::Codec,
+        pub role: data::Role,
+        /// To take back the place of a player that lost its connection.
+        pub token: Option<u64>,
     }
 
     impl From<JoinRequest> for RequestFromSlave {
         fn from(r: JoinRequest) -> Self {
-            let JoinRequest { name } = r;
-            Self { name }
+            let JoinRequest {
+                version: _,
+                name,
+                codec: _,
+                role,
+                token,
+            } = r;
+            let role = role.into();
+            let token = token.map(RejoinToken);
+            Self { name, role, token }
         }
     }
 }
@@ -61,18 +94,51 @@ pub mod from_slave {
 pub mod from_master {
     use super::*;
 
+    #[derive(Serialize, Deserialize, Debug)]
+    pub enum MessageSentByMaster {
+        /// Kept as the first variant, so slaves of every version can read why they can't join.
+        Rejection(Rejection),
+        Update(Update),
+        /// The reply to a slave that may join.
+        Welcome(Welcome),
+        Chat(data::ChatLine),
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct Welcome {
+        /// Spectators don't get a token, as they have no place to take back.
+        pub token: Option<u64>,
+        /// Which cells are part of the minefield, when not all of them are. It is only sent
+        /// along with the welcome, and applies to every update after.
+        pub mask: Option<Vec<bool>>,
+        pub update: Update,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct Rejection {
+        pub version: u16,
+        pub reason: data::Reason,
+    }
+
     #[derive(Serialize, Deserialize, Debug)]
     pub struct Update {
-        pub user: data::UID,
+        /// The receiving player, or none for a spectator.
+        pub user: Option<data::UID>,
         pub session: data::SID,
         pub dimensions: data::Dimensions,
         pub status: data::Status,
         pub updates: data::Updates,
         pub users: data::Users,
+        pub spectators: Vec<String>,
+        /// None when there is only a single team.
+        pub standings: Option<data::Standings>,
     }
 
-    impl From<Update> for UpdateFromMaster {
-        fn from(update: Update) -> Self {
+    /// Fails when the mask doesn't fit the minefield.
+    impl TryFrom<(Update, Option<Vec<bool>>)> for UpdateFromMaster {
+        type Error = &'static str;
+
+        fn try_from((update, mask): (Update, Option<Vec<bool>>)) -> Result<Self, Self::Error> {
             let Update {
                 user: _,
                 session,
@@ -80,27 +146,30 @@ pub mod from_master {
                 status,
                 updates,
                 users,
+                spectators,
+                standings,
             } = update;
             let (stats, names) = users.into();
-            let coords = dimensions.into();
+            let coords: cd::Coordinations = (dimensions, mask).try_into()?;
             let client = UpdateFromMasterForClient {
-                coords,
+                coords: coords.clone(),
                 updates: updates.into(),
             };
-            let namer = UpdateFromMasterForNamer { names };
+            let namer = UpdateFromMasterForNamer { names, spectators };
             let slave = GameUpdateFromMasterForSlave {
                 coords,
                 stats,
                 session: session.into(),
                 status: status.into(),
+                standings: standings.map(|standings| standings.into()),
             };
             let slave = UpdateFromMasterForSlave { game: slave, namer };
-            Self { client, slave }
+            Ok(Self { client, slave })
         }
     }
 
-    impl From<(UpdateFromMaster, SessionUserID)> for Update {
-        fn from((update, uid): (UpdateFromMaster, SessionUserID)) -> Self {
+    impl From<(UpdateFromMaster, Option<SessionUserID>)> for Update {
+        fn from((update, uid): (UpdateFromMaster, Option<SessionUserID>)) -> Self {
             let UpdateFromMaster { client, slave } = update;
             let UpdateFromMasterForClient { coords, updates } = client;
             let UpdateFromMasterForSlave { game, namer } = slave;
@@ -109,16 +178,19 @@ pub mod from_master {
                 stats,
                 session,
                 status,
+                standings,
             } = game;
-            let UpdateFromMasterForNamer { names } = namer;
+            let UpdateFromMasterForNamer { names, spectators } = namer;
             let users = (stats, names).into();
             Self {
-                user: uid.into(),
+                user: uid.map(|uid| uid.into()),
                 session: session.into(),
                 dimensions: coords.into(),
                 status: status.into(),
                 updates: updates.into(),
                 users,
+                spectators,
+                standings: standings.map(|standings| standings.into()),
             }
         }
     }
@@ -162,16 +234,58 @@ pub mod data {
         }
     }
 
+    #[derive(Serialize, Deserialize, Debug)]
+    pub enum Topology {
+        Rectangular,
+        Hexagonal,
+        Toroidal,
+    }
+
+    impl From<Topology> for cd::Topology {
+        fn from(topology: Topology) -> Self {
+            match topology {
+                Topology::Rectangular => Self::Rectangular,
+                Topology::Hexagonal => Self::Hexagonal,
+                Topology::Toroidal => Self::Toroidal,
+            }
+        }
+    }
+
+    impl From<cd::Topology> for Topology {
+        fn from(topology: cd::Topology) -> Self {
+            match topology {
+                cd::Topology::Rectangular => Self::Rectangular,
+                cd::Topology::Hexagonal => Self::Hexagonal,
+                cd::Topology::Toroidal => Self::Toroidal,
+            }
+        }
+    }
+
     #[derive(Serialize, Deserialize, Debug)]
     pub struct Dimensions {
         width: u32,
         height: u32,
-    }
-
-    impl From<Dimensions> for cd::Coordinations {
-        fn from(dimensions: Dimensions) -> Self {
-            let Dimensions { width, height } = dimensions;
-            Self::from_width_and_height(width, height)
+        topology: Topology,
+    }
+
+    /// Fails when the mask doesn't fit the minefield.
+    impl TryFrom<(Dimensions, Option<Vec<bool>>)> for cd::Coordinations {
+        type Error = &'static str;
+
+        fn try_from(
+            (dimensions, mask): (Dimensions, Option<Vec<bool>>),
+        ) -> Result<Self, Self::Error> {
+            let Dimensions {
+                width,
+                height,
+                topology,
+            } = dimensions;
+            let coords = Self::from_width_and_height(width, height).with_topology(topology.into());
+            match mask {
+                Some(mask) if mask.len() != coords.size() => Err("Mask doesn't fit the minefield"),
+                Some(mask) => Ok(coords.with_mask(mask)),
+                None => Ok(coords),
+            }
         }
     }
 
@@ -179,7 +293,12 @@ pub mod data {
         fn from(coords: cd::Coordinations) -> Self {
             let width = coords.columns();
             let height = coords.rows();
-            Self { width, height }
+            let topology = coords.topology().into();
+            Self {
+                width,
+                height,
+                topology,
+            }
         }
     }
 
@@ -203,6 +322,33 @@ pub mod data {
         }
     }
 
+    #[derive(Serialize, Deserialize, Debug)]
+    pub enum Action {
+        Uncover,
+        ToggleMark,
+        Chord,
+    }
+
+    impl From<Action> for super::Action {
+        fn from(action: Action) -> Self {
+            match action {
+                Action::Uncover => Self::Uncover,
+                Action::ToggleMark => Self::ToggleMark,
+                Action::Chord => Self::Chord,
+            }
+        }
+    }
+
+    impl From<super::Action> for Action {
+        fn from(action: super::Action) -> Self {
+            match action {
+                super::Action::Uncover => Self::Uncover,
+                super::Action::ToggleMark => Self::ToggleMark,
+                super::Action::Chord => Self::Chord,
+            }
+        }
+    }
+
     #[derive(Serialize, Deserialize, Debug)]
     pub struct Hint(u8);
 
@@ -288,7 +434,9 @@ pub mod data {
     #[derive(Serialize, Deserialize, Debug)]
     pub struct Stats {
         mines_left: i32,
-        lives_left: u32,
+        lives_left: Option<u32>,
+        clock: Clock,
+        three_bv: Option<u32>,
     }
 
     impl From<Stats> for super::Stats {
@@ -296,10 +444,14 @@ pub mod data {
             let Stats {
                 mines_left,
                 lives_left,
+                clock,
+                three_bv,
             } = stats;
             Self {
                 mines_left,
                 lives_left,
+                clock: clock.into(),
+                three_bv,
             }
         }
     }
@@ -309,10 +461,44 @@ pub mod data {
             let super::Stats {
                 mines_left,
                 lives_left,
+                clock,
+                three_bv,
             } = stats;
             Self {
                 mines_left,
                 lives_left,
+                clock: clock.into(),
+                three_bv,
+            }
+        }
+    }
+
+    /// The elapsed time in milliseconds.
+    #[derive(Serialize, Deserialize, Debug)]
+    pub enum Clock {
+        Idle,
+        Running(u64),
+        Stopped(u64),
+    }
+
+    impl From<Clock> for super::Clock {
+        fn from(clock: Clock) -> Self {
+            let duration = std::time::Duration::from_millis;
+            match clock {
+                Clock::Idle => Self::Idle,
+                Clock::Running(ms) => Self::Running(duration(ms)),
+                Clock::Stopped(ms) => Self::Stopped(duration(ms)),
+            }
+        }
+    }
+
+    impl From<super::Clock> for Clock {
+        fn from(clock: super::Clock) -> Self {
+            let millis = |duration: std::time::Duration| duration.as_millis() as u64;
+            match clock {
+                super::Clock::Idle => Self::Idle,
+                super::Clock::Running(duration) => Self::Running(millis(duration)),
+                super::Clock::Stopped(duration) => Self::Stopped(millis(duration)),
             }
         }
     }
@@ -360,9 +546,18 @@ pub mod data {
     pub struct User {
         id: UID,
         name: String,
+        connected: bool,
         marked_correct: u32,
         marked_incorrect: u32,
         exploded: u32,
+        revealed: u32,
+        hints: u32,
+        openings: u32,
+        biggest_opening: u32,
+        effective_clicks: u32,
+        wasted_clicks: u32,
+        lives_left: Option<u32>,
+        score: i32,
     }
 
     impl From<User> for ((SessionUserID, UserStat), UserName) {
@@ -370,17 +565,42 @@ pub mod data {
             let User {
                 id,
                 name,
+                connected,
                 marked_correct,
                 marked_incorrect,
                 exploded,
+                revealed,
+                hints,
+                openings,
+                biggest_opening,
+                effective_clicks,
+                wasted_clicks,
+                lives_left,
+                score,
             } = user;
             let uid = id.into();
+            let connection = match connected {
+                true => Connection::Connected,
+                false => Connection::Lost,
+            };
             let stat = UserStat {
                 marked_correct,
                 marked_incorrect,
                 exploded,
+                revealed,
+                hints,
+                openings,
+                biggest_opening,
+                effective_clicks,
+                wasted_clicks,
+                lives_left,
+                score,
+            };
+            let username = UserName {
+                uid,
+                name,
+                connection,
             };
-            let username = UserName { uid, name };
             ((uid, stat), username)
         }
     }
@@ -388,18 +608,40 @@ pub mod data {
     impl From<(SessionUserID, UserStat, UserName)> for User {
         fn from((uid, stat, name): (SessionUserID, UserStat, UserName)) -> Self {
             let id = uid.into();
-            let UserName { uid: _, name } = name;
+            let UserName {
+                uid: _,
+                name,
+                connection,
+            } = name;
+            let connected = connection == Connection::Connected;
             let UserStat {
                 marked_correct,
                 marked_incorrect,
                 exploded,
+                revealed,
+                hints,
+                openings,
+                biggest_opening,
+                effective_clicks,
+                wasted_clicks,
+                lives_left,
+                score,
             } = stat;
             Self {
                 id,
                 name,
+                connected,
                 marked_correct,
                 marked_incorrect,
                 exploded,
+                revealed,
+                hints,
+                openings,
+                biggest_opening,
+                effective_clicks,
+                wasted_clicks,
+                lives_left,
+                score,
             }
         }
     }
@@ -436,6 +678,108 @@ pub mod data {
         }
     }
 
+    /// New reasons are only ever added at the end.
+    #[derive(Serialize, Deserialize, Debug)]
+    pub enum Reason {
+        VersionMismatch,
+        GameFull,
+        NameTaken,
+    }
+
+    impl From<Reason> for Rejection {
+        fn from(reason: Reason) -> Self {
+            match reason {
+                Reason::VersionMismatch => Self::VersionMismatch,
+                Reason::GameFull => Self::GameFull,
+                Reason::NameTaken => Self::NameTaken,
+            }
+        }
+    }
+
+    impl From<Rejection> for Reason {
+        fn from(rejection: Rejection) -> Self {
+            match rejection {
+                Rejection::VersionMismatch => Self::VersionMismatch,
+                Rejection::GameFull => Self::GameFull,
+                Rejection::NameTaken => Self::NameTaken,
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct ChatLine {
+        user: UID,
+        text: String,
+    }
+
+    impl From<ChatLine> for chat::ChatLine {
+        fn from(line: ChatLine) -> Self {
+            let ChatLine { user, text } = line;
+            Self {
+                uid: user.into(),
+                text,
+            }
+        }
+    }
+
+    impl From<chat::ChatLine> for ChatLine {
+        fn from(line: chat::ChatLine) -> Self {
+            let chat::ChatLine { uid, text } = line;
+            Self {
+                user: uid.into(),
+                text,
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+    pub enum Role {
+        Player,
+        Spectator,
+    }
+
+    impl From<Role> for super::Role {
+        fn from(role: Role) -> Self {
+            match role {
+                Role::Player => Self::Player,
+                Role::Spectator => Self::Spectator,
+            }
+        }
+    }
+
+    impl From<super::Role> for Role {
+        fn from(role: super::Role) -> Self {
+            match role {
+                super::Role::Player => Self::Player,
+                super::Role::Spectator => Self::Spectator,
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+    pub enum Codec {
+        Binary,
+        Json,
+    }
+
+    impl From<Codec> for codec::Codec {
+        fn from(codec: Codec) -> Self {
+            match codec {
+                Codec::Binary => Self::Binary,
+                Codec::Json => Self::Json,
+            }
+        }
+    }
+
+    impl From<codec::Codec> for Codec {
+        fn from(codec: codec::Codec) -> Self {
+            match codec {
+                codec::Codec::Binary => Self::Binary,
+                codec::Codec::Json => Self::Json,
+            }
+        }
+    }
+
     #[derive(Serialize, Deserialize, Debug)]
     pub enum Status {
         Playing,
@@ -462,4 +806,105 @@ pub mod data {
             }
         }
     }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+    pub struct Team(u8);
+
+    impl From<Team> for super::Team {
+        fn from(team: Team) -> Self {
+            let Team(team) = team;
+            Self(team)
+        }
+    }
+
+    impl From<super::Team> for Team {
+        fn from(team: super::Team) -> Self {
+            let super::Team(team) = team;
+            Self(team)
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct TeamProgress {
+        team: Team,
+        players: Vec<UID>,
+        uncovered: u32,
+        safe: u32,
+        lives_left: Option<u32>,
+    }
+
+    impl From<TeamProgress> for super::TeamProgress {
+        fn from(progress: TeamProgress) -> Self {
+            let TeamProgress {
+                team,
+                players,
+                uncovered,
+                safe,
+                lives_left,
+            } = progress;
+            Self {
+                team: team.into(),
+                players: players.into_iter().map(|uid| uid.into()).collect(),
+                uncovered,
+                safe,
+                lives_left,
+            }
+        }
+    }
+
+    impl From<super::TeamProgress> for TeamProgress {
+        fn from(progress: super::TeamProgress) -> Self {
+            let super::TeamProgress {
+                team,
+                players,
+                uncovered,
+                safe,
+                lives_left,
+            } = progress;
+            Self {
+                team: team.into(),
+                players: players.into_iter().map(|uid| uid.into()).collect(),
+                uncovered,
+                safe,
+                lives_left,
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct Standings {
+        shown: Team,
+        teams: Vec<TeamProgress>,
+        winner: Option<Team>,
+    }
+
+    impl From<Standings> for super::Standings {
+        fn from(standings: Standings) -> Self {
+            let Standings {
+                shown,
+                teams,
+                winner,
+            } = standings;
+            Self {
+                shown: shown.into(),
+                teams: teams.into_iter().map(|team| team.into()).collect(),
+                winner: winner.map(|team| team.into()),
+            }
+        }
+    }
+
+    impl From<super::Standings> for Standings {
+        fn from(standings: super::Standings) -> Self {
+            let super::Standings {
+                shown,
+                teams,
+                winner,
+            } = standings;
+            Self {
+                shown: shown.into(),
+                teams: teams.into_iter().map(|team| team.into()).collect(),
+                winner: winner.map(|team| team.into()),
+            }
+        }
+    }
 }
@@ -1,72 +1,159 @@
+mod codec;
+mod heartbeat;
 mod messages;
+mod reliable;
+mod transport;
 
 use super::*;
 
 use messages as msg;
 
-use std::net::UdpSocket;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
 
 const SOCKET_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(5);
-const BUFFER_FIXED_SIZE: usize = 1 << 22;
+/// How long a slave waits for a reply to its join request, before sending it again.
+const JOIN_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// Larger packets are dropped.
+const MAX_PACKET_SIZE: usize = 1 << 22;
 
 pub enum MessageSent {
     Yes,
     No,
 }
 
-pub struct MessengerBuffer(Vec<u8>);
+pub use codec::Codec;
+pub use transport::Transport;
+
+/// Memory to encode messages into, and to receive messages in.
+pub struct MessengerBuffer {
+    outgoing: Vec<u8>,
+    incoming: Vec<u8>,
+}
 
 pub struct Messenger {
-    socket: UdpSocket,
+    channel: Box<dyn transport::Channel>,
+    /// The codec this side prefers.
+    codec: Codec,
+    /// The codecs that were agreed upon with the slaves that joined.
+    peer_codecs: Mutex<HashMap<std::net::SocketAddr, Codec>>,
+    outbox: Mutex<reliable::Outbox>,
+    inbox: Mutex<reliable::Inbox<Part>>,
+    /// The parts of messages of which the last part didn't arrive yet, per peer and stream.
+    parts: Mutex<HashMap<std::net::SocketAddr, (reliable::Stream, Vec<u8>)>>,
+    /// The number of packets that were dropped because they couldn't be read.
+    malformed: AtomicUsize,
+    heartbeats: Mutex<heartbeat::Heartbeats>,
+    /// The mask of the minefield, which a slave receives along with the welcome of its master.
+    mask: Mutex<Option<Vec<bool>>>,
 }
 
 impl Messenger {
-    pub fn new(socket: UdpSocket) -> Self {
-        socket
-            .set_read_timeout(Some(SOCKET_TIMEOUT))
-            .expect("Failed to set socket read timeout");
-        socket
-            .set_write_timeout(Some(SOCKET_TIMEOUT))
-            .expect("Failed to set socket read timeout");
-        Self { socket }
+    /// A peer which stays silent for longer than `timeout` is lost.
+    pub fn new(
+        channel: Box<dyn transport::Channel>,
+        codec: Codec,
+        timeout: std::time::Duration,
+    ) -> Self {
+        let peer_codecs = Default::default();
+        let outbox = Default::default();
+        let inbox = Default::default();
+        let parts = Default::default();
+        let malformed = AtomicUsize::new(0);
+        let heartbeats = Mutex::new(heartbeat::Heartbeats::new(timeout));
+        let mask = Mutex::new(None);
+        Self {
+            channel,
+            codec,
+            peer_codecs,
+            outbox,
+            inbox,
+            parts,
+            malformed,
+            heartbeats,
+            mask,
+        }
     }
 
     pub fn request_to_join(
         &self,
         buffer: &mut MessengerBuffer,
         player_name: &str,
-    ) -> UpdateFromMaster {
+        role: Role,
+        token: Option<RejoinToken>,
+    ) -> Result<(UpdateFromMaster, Option<RejoinToken>), Rejection> {
         let msg = msg::from_slave::JoinRequest {
+            version: msg::VERSION,
             name: player_name.into(),
+            codec: self.codec.into(),
+            role: role.into(),
+            token: token.map(|RejoinToken(token)| token),
         };
         let msg = msg::from_slave::MessageSentByClient::Join(msg);
+        let mut sent: Option<Instant> = None;
         loop {
-            println!("Sending a join request");
-            if let MessageSent::Yes = self.send_packet_from_slave(buffer, &msg).unwrap() {
-                if let Some(update) = self.receive_packet_from_master(buffer) {
+            if sent.is_none_or(|sent| sent.elapsed() >= JOIN_RETRY_INTERVAL) {
+                println!("Sending a join request");
+                self.send_packet_from_slave(buffer, &msg).unwrap();
+                sent = Some(Instant::now());
+            }
+            match self.receive_message_from_master(buffer) {
+                Some((msg::from_master::MessageSentByMaster::Welcome(welcome), addr)) => {
                     println!("Received a join reply");
-                    return update;
+                    let msg::from_master::Welcome {
+                        token,
+                        mask,
+                        update,
+                    } = welcome;
+                    if let Some(update) = self.welcomed(update, mask, addr) {
+                        self.watch(addr);
+                        return Ok((update, token.map(RejoinToken)));
+                    }
                 }
+                Some((msg::from_master::MessageSentByMaster::Rejection(rejection), _)) => {
+                    return Err(rejection.reason.into());
+                }
+                Some((msg::from_master::MessageSentByMaster::Update(_), _))
+                | Some((msg::from_master::MessageSentByMaster::Chat(_), _))
+                | None => {}
             }
+            self.retransmit();
         }
     }
 
     pub fn left_click(&self, buffer: &mut MessengerBuffer, session: SessionID, coord: &Coord) {
-        let left = true;
-        self.click(buffer, session, coord, left)
+        self.click(buffer, session, coord, Action::Uncover)
     }
     pub fn right_click(&self, buffer: &mut MessengerBuffer, session: SessionID, coord: &Coord) {
-        let left = false;
-        self.click(buffer, session, coord, left)
+        self.click(buffer, session, coord, Action::ToggleMark)
+    }
+    pub fn chord(&self, buffer: &mut MessengerBuffer, session: SessionID, coord: &Coord) {
+        self.click(buffer, session, coord, Action::Chord)
     }
 
-    fn click(&self, buffer: &mut MessengerBuffer, session: SessionID, coord: &Coord, left: bool) {
+    fn click(
+        &self,
+        buffer: &mut MessengerBuffer,
+        session: SessionID,
+        coord: &Coord,
+        action: Action,
+    ) {
         let msg = msg::from_slave::Click {
             session: session.into(),
             coord: (*coord).into(),
-            left,
+            action: action.into(),
         };
         let msg = msg::from_slave::MessageSentByClient::Click(msg);
+        // a click that doesn't make it through gets retransmitted
+        self.send_packet_from_slave(buffer, &msg).unwrap();
+    }
+
+    pub fn chat(&self, buffer: &mut MessengerBuffer, text: &str) {
+        let msg = msg::from_slave::Chat { text: text.into() };
+        let msg = msg::from_slave::MessageSentByClient::Chat(msg);
+        // a message that doesn't make it through gets retransmitted
         self.send_packet_from_slave(buffer, &msg).unwrap();
     }
 
@@ -75,83 +162,453 @@ impl Messenger {
         buffer: &mut MessengerBuffer,
         msg: &msg::from_slave::MessageSentByClient,
     ) -> Result<MessageSent, &'static str> {
-        let MessengerBuffer(b) = buffer;
-        if serde_json::to_writer(b, &msg).is_err() {
-            let MessengerBuffer(b) = buffer;
-            b.clear();
-            return Err("failed to write packet to memory");
-        }
-        let MessengerBuffer(b) = buffer;
-        let err = match self.socket.send(&b[..]) {
-            Ok(s) if s == b[..].len() => Ok(MessageSent::Yes),
-            Ok(_) => Err("Failed to send entire packet over ip"),
-            Err(_) => Ok(MessageSent::No),
-        };
-        b.clear();
-        err
+        let addr = self
+            .channel
+            .peer_addr()
+            .map_err(|_| "Slave socket is not connected to its master")?;
+        self.send_reliably(buffer, addr, self.codec, msg)
     }
 
     pub fn send_updates_from_master(
         &self,
         buffer: &mut MessengerBuffer,
         addr: std::net::SocketAddr,
-        uid: SessionUserID,
+        uid: Option<SessionUserID>,
         msg: UpdateFromMaster,
     ) -> Result<MessageSent, &'static str> {
         let msg: msg::from_master::Update = (msg, uid).into();
-        let MessengerBuffer(b) = buffer;
-        if serde_json::to_writer(b, &msg).is_err() {
-            let MessengerBuffer(b) = buffer;
-            b.clear();
-            return Err("failed to write packet to memory");
-        }
-        let MessengerBuffer(b) = buffer;
-        let err = match self.socket.send_to(b, addr) {
-            Ok(s) if s == b[..].len() => Ok(MessageSent::Yes),
+        let msg = msg::from_master::MessageSentByMaster::Update(msg);
+        self.send_reliably(buffer, addr, self.peer_codec(addr), &msg)
+    }
+
+    /// Relays what a player said.
+    pub fn send_chat_from_master(
+        &self,
+        buffer: &mut MessengerBuffer,
+        addr: std::net::SocketAddr,
+        line: chat::ChatLine,
+    ) -> Result<MessageSent, &'static str> {
+        let msg = msg::from_master::MessageSentByMaster::Chat(line.into());
+        self.send_reliably(buffer, addr, self.peer_codec(addr), &msg)
+    }
+
+    /// Lets a slave join, and gives it the whole field.
+    pub fn welcome(
+        &self,
+        buffer: &mut MessengerBuffer,
+        addr: std::net::SocketAddr,
+        uid: Option<SessionUserID>,
+        token: Option<RejoinToken>,
+        msg: UpdateFromMaster,
+    ) -> Result<MessageSent, &'static str> {
+        let token = token.map(|RejoinToken(token)| token);
+        let mask = msg.client.coords.mask().map(<[bool]>::to_vec);
+        let update = (msg, uid).into();
+        let msg = msg::from_master::Welcome {
+            token,
+            mask,
+            update,
+        };
+        let msg = msg::from_master::MessageSentByMaster::Welcome(msg);
+        self.send_reliably(buffer, addr, self.peer_codec(addr), &msg)
+    }
+
+    /// Tells a slave why it can't join.
+    pub fn reject(
+        &self,
+        buffer: &mut MessengerBuffer,
+        addr: std::net::SocketAddr,
+        rejection: Rejection,
+    ) -> Result<MessageSent, &'static str> {
+        self.send_rejection(buffer, addr, self.peer_codec(addr), rejection)
+    }
+
+    /// The codec which the slave at `addr` asked for when it joined.
+    fn peer_codec(&self, addr: std::net::SocketAddr) -> Codec {
+        self.peer_codecs
+            .lock()
+            .expect("Failed to lock codecs")
+            .get(&addr)
+            .copied()
+            .unwrap_or(self.codec)
+    }
+
+    fn send_rejection(
+        &self,
+        buffer: &mut MessengerBuffer,
+        addr: std::net::SocketAddr,
+        codec: Codec,
+        rejection: Rejection,
+    ) -> Result<MessageSent, &'static str> {
+        let msg = msg::from_master::Rejection {
+            version: msg::VERSION,
+            reason: rejection.into(),
+        };
+        let msg = msg::from_master::MessageSentByMaster::Rejection(msg);
+        self.send_reliably(buffer, addr, codec, &msg)
+    }
+
+    /// Sends a message, and keeps it until the peer acknowledges it unless the channel is
+    /// reliable by itself. A message which is too large for the channel is sent in parts.
+    fn send_reliably<M>(
+        &self,
+        buffer: &mut MessengerBuffer,
+        addr: std::net::SocketAddr,
+        codec: Codec,
+        msg: &M,
+    ) -> Result<MessageSent, &'static str>
+    where
+        M: serde::Serialize,
+    {
+        let payload = &mut buffer.outgoing;
+        payload.clear();
+        codec.encode(msg, payload)?;
+        if codec::MESSAGE_HEADER_SIZE + payload.len() > MAX_PACKET_SIZE {
+            return Err("Message too large");
+        }
+        // only a message that gets sent takes sequence numbers, as the peer waits for every one
+        let mut outbox = self.outbox.lock().expect("Failed to lock outbox");
+        let part_size = self.channel.max_message_size() - codec::MESSAGE_HEADER_SIZE;
+        let parts = payload.len().div_ceil(part_size).max(1);
+        let datagrams: Vec<Vec<u8>> = (0..parts)
+            .map(|part| {
+                let payload = &payload[part * part_size..payload.len().min((part + 1) * part_size)];
+                let (stream, seq) = outbox.next_seq(addr);
+                let last = part + 1 == parts;
+                let mut datagram = Vec::with_capacity(codec::MESSAGE_HEADER_SIZE + payload.len());
+                codec::Envelope::write_message_header(stream, seq, codec, last, &mut datagram);
+                datagram.extend_from_slice(payload);
+                if !self.channel.is_reliable() {
+                    outbox.keep(addr, seq, datagram.clone(), Instant::now());
+                }
+                datagram
+            })
+            .collect();
+        drop(outbox);
+        let mut sent = MessageSent::Yes;
+        for datagram in datagrams {
+            if let MessageSent::No = self.send_datagram(&datagram, addr)? {
+                sent = MessageSent::No;
+            }
+        }
+        Ok(sent)
+    }
+
+    fn send_ack(&self, addr: std::net::SocketAddr, stream: u32, seq: u64) {
+        let mut datagram = Vec::new();
+        codec::Envelope::write_ack(stream, seq, &mut datagram);
+        // a lost ack gets resent when the message is retransmitted
+        let _ = self.send_datagram(&datagram, addr);
+    }
+
+    fn send_datagram(
+        &self,
+        datagram: &[u8],
+        addr: std::net::SocketAddr,
+    ) -> Result<MessageSent, &'static str> {
+        match self.channel.send_to(datagram, addr) {
+            Ok(s) if s == datagram.len() => Ok(MessageSent::Yes),
             Ok(_) => Err("Failed to send entire packet over ip"),
             Err(_) => Ok(MessageSent::No),
-        };
-        let MessengerBuffer(b) = buffer;
-        b.clear();
-        err
+        }
+    }
+
+    /// Sends the messages again which haven't been acknowledged in time.
+    pub fn retransmit(&self) {
+        let due = self
+            .outbox
+            .lock()
+            .expect("Failed to lock outbox")
+            .due(Instant::now());
+        for (addr, datagram) in due {
+            let _ = self.send_datagram(&datagram, addr);
+        }
+    }
+
+    /// Whether or not every message that was sent has been acknowledged.
+    pub fn flushed(&self) -> bool {
+        self.outbox
+            .lock()
+            .expect("Failed to lock outbox")
+            .is_empty()
+    }
+
+    /// Starts sending heartbeats to `addr`, and expects them back.
+    pub fn watch(&self, addr: std::net::SocketAddr) {
+        self.heartbeats
+            .lock()
+            .expect("Failed to lock heartbeats")
+            .watch(addr, Instant::now());
+    }
+
+    /// Sends heartbeats to the peers that are watched, and returns the peers which have been
+    /// silent for too long. Those are forgotten, so they can join again as if they were new.
+    pub fn check_peers(&self) -> Vec<std::net::SocketAddr> {
+        let now = Instant::now();
+        let mut heartbeats = self.heartbeats.lock().expect("Failed to lock heartbeats");
+        let due = heartbeats.due(now);
+        let lost = heartbeats.lost(now);
+        drop(heartbeats);
+        let mut datagram = Vec::new();
+        codec::Envelope::write_heartbeat(&mut datagram);
+        for addr in due {
+            let _ = self.send_datagram(&datagram, addr);
+        }
+        for &addr in &lost {
+            self.outbox
+                .lock()
+                .expect("Failed to lock outbox")
+                .forget(addr);
+            self.inbox
+                .lock()
+                .expect("Failed to lock inbox")
+                .forget(addr);
+            self.parts
+                .lock()
+                .expect("Failed to lock parts")
+                .remove(&addr);
+            self.peer_codecs
+                .lock()
+                .expect("Failed to lock codecs")
+                .remove(&addr);
+        }
+        lost
     }
 
     pub fn receive_packet_from_master(
         &self,
         buffer: &mut MessengerBuffer,
+    ) -> Option<MessageFromMaster> {
+        let (msg, addr) = self.receive_message_from_master(buffer)?;
+        match msg {
+            msg::from_master::MessageSentByMaster::Update(update) => {
+                let mask = self.mask.lock().expect("Failed to lock mask").clone();
+                let update = self.update_from_master(update, mask, addr)?;
+                Some(MessageFromMaster::Update(Box::new(update)))
+            }
+            msg::from_master::MessageSentByMaster::Welcome(welcome) => {
+                let msg::from_master::Welcome { mask, update, .. } = welcome;
+                let update = self.welcomed(update, mask, addr)?;
+                Some(MessageFromMaster::Update(Box::new(update)))
+            }
+            msg::from_master::MessageSentByMaster::Chat(line) => {
+                Some(MessageFromMaster::Chat(line.into()))
+            }
+            // only a slave that is joining can be rejected
+            msg::from_master::MessageSentByMaster::Rejection(_) => None,
+        }
+    }
+
+    /// Keeps the mask which the master sent along with its welcome, for the updates after.
+    fn welcomed(
+        &self,
+        update: msg::from_master::Update,
+        mask: Option<Vec<bool>>,
+        addr: std::net::SocketAddr,
+    ) -> Option<UpdateFromMaster> {
+        let update = self.update_from_master(update, mask.clone(), addr)?;
+        *self.mask.lock().expect("Failed to lock mask") = mask;
+        Some(update)
+    }
+
+    fn update_from_master(
+        &self,
+        update: msg::from_master::Update,
+        mask: Option<Vec<bool>>,
+        addr: std::net::SocketAddr,
     ) -> Option<UpdateFromMaster> {
-        self.receive_packet::<msg::from_master::Update>(buffer)
-            .map(|(u, _addr)| u.into())
+        (update, mask)
+            .try_into()
+            .map_err(|err| self.drop_malformed(addr, err))
+            .ok()
+    }
+
+    fn receive_message_from_master(
+        &self,
+        buffer: &mut MessengerBuffer,
+    ) -> Option<(msg::from_master::MessageSentByMaster, std::net::SocketAddr)> {
+        let ((codec, payload), addr) = self.receive_payload(buffer)?;
+        codec
+            .decode(&payload)
+            .map_err(|err| self.drop_malformed(addr, err))
+            .ok()
+            .map(|msg| (msg, addr))
     }
 
     pub fn receive_packet_from_slave(
         &self,
         buffer: &mut MessengerBuffer,
     ) -> Option<(MessageFromSlave, std::net::SocketAddr)> {
-        self.receive_packet::<msg::from_slave::MessageSentByClient>(buffer)
-            .map(|(msg, addr)| (msg.into(), addr))
+        let ((codec, payload), addr) = self.receive_payload(buffer)?;
+        let msg: msg::from_slave::MessageSentByClient = match codec.decode(&payload) {
+            Ok(msg) => msg,
+            Err(err) => {
+                match codec.decode(&payload) {
+                    Ok(msg::from_slave::Handshake::Join { version }) if version != msg::VERSION => {
+                        self.reject_version(buffer, addr, codec, version)
+                    }
+                    _ => self.drop_malformed(addr, err),
+                }
+                return None;
+            }
+        };
+        if let msg::from_slave::MessageSentByClient::Join(join) = &msg {
+            if join.version != msg::VERSION {
+                self.reject_version(buffer, addr, codec, join.version);
+                return None;
+            }
+            let codec = self.codec.negotiate(join.codec.into());
+            self.peer_codecs
+                .lock()
+                .expect("Failed to lock codecs")
+                .insert(addr, codec);
+        }
+        Some((msg.into(), addr))
     }
 
-    fn receive_packet<M>(&self, buffer: &mut MessengerBuffer) -> Option<(M, std::net::SocketAddr)>
-    where
-        for<'a> M: serde::Deserialize<'a>,
-    {
-        let MessengerBuffer(buffer) = buffer;
-        buffer.resize(BUFFER_FIXED_SIZE, 0);
-        let (size, addr) = self.socket.recv_from(buffer).ok()?;
-        let b = &buffer[..size];
-        let packet = serde_json::from_reader(b)
-            .map(|msg| (msg, addr))
-            .expect("Failed to parse packet");
-        buffer.clear();
-        Some(packet)
+    fn reject_version(
+        &self,
+        buffer: &mut MessengerBuffer,
+        addr: std::net::SocketAddr,
+        codec: Codec,
+        version: u16,
+    ) {
+        println!(
+            "Rejected {addr}, which speaks version {version} instead of {}",
+            msg::VERSION
+        );
+        self.send_rejection(buffer, addr, codec, Rejection::VersionMismatch)
+            .unwrap();
+    }
+
+    /// Returns the next payload in order, if any. Acknowledgements and messages which were
+    /// received before are dealt with here.
+    fn receive_payload(
+        &self,
+        buffer: &mut MessengerBuffer,
+    ) -> Option<((Codec, Vec<u8>), std::net::SocketAddr)> {
+        let next = || self.inbox.lock().expect("Failed to lock inbox").next();
+        loop {
+            let (part, addr) = match next() {
+                Some(next) => next,
+                None => {
+                    self.receive_datagram(buffer);
+                    next()?
+                }
+            };
+            if let Some(payload) = self.assemble(part, addr) {
+                return Some((payload, addr));
+            }
+        }
+    }
+
+    /// Puts the parts of a message back together, and returns the payload once it is complete.
+    fn assemble(&self, part: Part, addr: std::net::SocketAddr) -> Option<(Codec, Vec<u8>)> {
+        let Part {
+            stream,
+            codec,
+            last,
+            payload,
+        } = part;
+        let mut parts = self.parts.lock().expect("Failed to lock parts");
+        // the parts which came before belong to this message, unless the peer started over
+        let mut whole = match parts.remove(&addr) {
+            Some((earlier, whole)) if earlier == stream => whole,
+            _ => Vec::new(),
+        };
+        whole.extend_from_slice(&payload);
+        if last {
+            return Some((codec, whole));
+        }
+        if whole.len() > MAX_PACKET_SIZE {
+            drop(parts);
+            self.drop_malformed(addr, "Message too large");
+            return None;
+        }
+        parts.insert(addr, (stream, whole));
+        None
+    }
+
+    fn receive_datagram(&self, buffer: &mut MessengerBuffer) {
+        let b = &mut buffer.incoming;
+        let (size, addr) = match self.channel.recv_from(b) {
+            Ok(received) => received,
+            Err(_) => return,
+        };
+        if size > MAX_PACKET_SIZE {
+            return self.drop_malformed(addr, "Packet too large");
+        }
+        let envelope = match codec::Envelope::read(&b[..size]) {
+            Ok(envelope) => envelope,
+            Err(err) => return self.drop_malformed(addr, err),
+        };
+        self.heartbeats
+            .lock()
+            .expect("Failed to lock heartbeats")
+            .heard(addr, Instant::now());
+        match envelope {
+            codec::Envelope::Message {
+                stream,
+                seq,
+                codec,
+                last,
+                payload,
+            } => {
+                let part = Part {
+                    stream,
+                    codec,
+                    last,
+                    payload: payload.to_vec(),
+                };
+                let received = self
+                    .inbox
+                    .lock()
+                    .expect("Failed to lock inbox")
+                    .accept(addr, stream, seq, part);
+                // a message which is dropped for being too early has to be sent again
+                if !self.channel.is_reliable() && received != reliable::Received::TooEarly {
+                    self.send_ack(addr, stream, seq);
+                }
+                if received == reliable::Received::Restarted {
+                    // what was sent before won't be understood anymore
+                    println!("{addr} started over");
+                    self.outbox
+                        .lock()
+                        .expect("Failed to lock outbox")
+                        .forget(addr);
+                }
+            }
+            codec::Envelope::Ack { stream, seq } => self
+                .outbox
+                .lock()
+                .expect("Failed to lock outbox")
+                .ack(addr, stream, seq),
+            codec::Envelope::Heartbeat => {}
+        }
+    }
+
+    fn drop_malformed(&self, addr: std::net::SocketAddr, err: &str) {
+        let count = self.malformed.fetch_add(1, Ordering::Relaxed) + 1;
+        println!("Dropped a malformed packet from {addr} ({count} so far): {err}");
     }
 }
 
+/// A message as it was received, or a part of it.
+struct Part {
+    stream: reliable::Stream,
+    codec: Codec,
+    /// Whether or not the message is complete with this part.
+    last: bool,
+    payload: Vec<u8>,
+}
+
 impl MessengerBuffer {
     pub fn new() -> Self {
-        let buffer = Vec::new();
-        Self(buffer)
+        let outgoing = Vec::new();
+        // allocated once, as it is reused for every message that is received
+        // one byte more than allowed, to notice packets which are too large
+        let incoming = vec![0; MAX_PACKET_SIZE + 1];
+        Self { outgoing, incoming }
     }
 }
 
@@ -193,3 +650,493 @@ impl Drop for MessengerThread {
             .unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+    fn click_reaches_master_once(transport: Transport, port: u16) {
+        let master_addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+        let slave_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
+        let master = Messenger::new(transport.listen(master_addr), Codec::Binary, TIMEOUT);
+        let slave = Messenger::new(
+            transport.connect(slave_addr, master_addr),
+            Codec::Json,
+            TIMEOUT,
+        );
+        let mut buffer = MessengerBuffer::new();
+        slave.left_click(&mut buffer, SessionID::new(3), &Coord { x: 1, y: 2 });
+        let received = std::iter::repeat_with(|| master.receive_packet_from_slave(&mut buffer))
+            .take(100)
+            .find_map(|received| received);
+        match received {
+            Some((MessageFromSlave::Action(action), _addr)) => {
+                let ActionFromSlave {
+                    session,
+                    coord,
+                    action,
+                } = action;
+                assert_eq!(session, SessionID::new(3));
+                assert_eq!(coord, Coord { x: 1, y: 2 });
+                assert!(matches!(action, Action::Uncover));
+            }
+            _ => panic!("Master didn't receive the click"),
+        }
+        // the slave got an ack, so nothing gets sent again
+        for _ in 0..10 {
+            let _ = slave.receive_packet_from_master(&mut buffer);
+        }
+        std::thread::sleep(reliable::RETRANSMIT_TIMEOUT);
+        slave.retransmit();
+        assert!(master.receive_packet_from_slave(&mut buffer).is_none());
+    }
+
+    #[test]
+    fn click_reaches_master_once_over_udp() {
+        click_reaches_master_once(Transport::Udp, 47811)
+    }
+
+    #[test]
+    fn click_reaches_master_once_over_tcp() {
+        click_reaches_master_once(Transport::Tcp, 47812)
+    }
+
+    #[test]
+    fn slave_of_another_version_is_rejected() {
+        /// A join request as a later version of the protocol could send it.
+        #[derive(serde::Serialize)]
+        enum FutureMessageSentByClient {
+            Join { version: u16, token: u64 },
+        }
+        for (codec, port) in [(Codec::Binary, 47813), (Codec::Json, 47814)] {
+            let master_addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+            let slave_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
+            let master = Messenger::new(Transport::Udp.listen(master_addr), Codec::Binary, TIMEOUT);
+            let slave = Messenger::new(
+                Transport::Udp.connect(slave_addr, master_addr),
+                codec,
+                TIMEOUT,
+            );
+            let mut buffer = MessengerBuffer::new();
+            let join = FutureMessageSentByClient::Join {
+                version: msg::VERSION + 1,
+                token: 7,
+            };
+            slave
+                .send_reliably(&mut buffer, master_addr, codec, &join)
+                .unwrap();
+            let rejection = std::iter::repeat_with(|| {
+                assert!(master.receive_packet_from_slave(&mut buffer).is_none());
+                slave.receive_message_from_master(&mut buffer)
+            })
+            .take(100)
+            .find_map(|received| received);
+            match rejection {
+                Some((msg::from_master::MessageSentByMaster::Rejection(rejection), _)) => {
+                    assert_eq!(rejection.version, msg::VERSION);
+                    assert_eq!(
+                        Rejection::from(rejection.reason),
+                        Rejection::VersionMismatch
+                    );
+                }
+                _ => panic!("Slave wasn't rejected"),
+            }
+        }
+    }
+
+    #[test]
+    fn garbage_is_dropped_and_counted() {
+        let master_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 47815));
+        let slave_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
+        let master = Messenger::new(Transport::Udp.listen(master_addr), Codec::Binary, TIMEOUT);
+        let slave = Messenger::new(
+            Transport::Udp.connect(slave_addr, master_addr),
+            Codec::Binary,
+            TIMEOUT,
+        );
+        let mut buffer = MessengerBuffer::new();
+        slave.send_datagram(b"garbage", master_addr).unwrap();
+        slave.left_click(&mut buffer, SessionID::new(3), &Coord { x: 1, y: 2 });
+        let received = std::iter::repeat_with(|| master.receive_packet_from_slave(&mut buffer))
+            .take(100)
+            .find_map(|received| received);
+        assert!(matches!(received, Some((MessageFromSlave::Action(_), _))));
+        assert_eq!(master.malformed.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn message_too_large_holds_nothing_back() {
+        let master_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 47818));
+        let slave_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
+        let master = Messenger::new(Transport::Udp.listen(master_addr), Codec::Binary, TIMEOUT);
+        let slave = Messenger::new(
+            Transport::Udp.connect(slave_addr, master_addr),
+            Codec::Binary,
+            TIMEOUT,
+        );
+        let mut buffer = MessengerBuffer::new();
+        let too_large = vec![0u8; MAX_PACKET_SIZE];
+        assert!(slave
+            .send_reliably(&mut buffer, master_addr, Codec::Binary, &too_large)
+            .is_err());
+        slave.left_click(&mut buffer, SessionID::new(3), &Coord { x: 1, y: 2 });
+        let received = std::iter::repeat_with(|| master.receive_packet_from_slave(&mut buffer))
+            .take(100)
+            .find_map(|received| received);
+        assert!(matches!(received, Some((MessageFromSlave::Action(_), _))));
+    }
+
+    #[test]
+    fn heartbeats_keep_peers_until_they_are_gone() {
+        let timeout = heartbeat::HEARTBEAT_INTERVAL * 2;
+        let master_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 47816));
+        let slave_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
+        let master = Messenger::new(Transport::Udp.listen(master_addr), Codec::Binary, timeout);
+        let slave = Messenger::new(
+            Transport::Udp.connect(slave_addr, master_addr),
+            Codec::Binary,
+            timeout,
+        );
+        let mut buffer = MessengerBuffer::new();
+        slave.watch(master_addr);
+        slave.left_click(&mut buffer, SessionID::new(3), &Coord { x: 1, y: 2 });
+        let (_, slave_addr) =
+            std::iter::repeat_with(|| master.receive_packet_from_slave(&mut buffer))
+                .take(100)
+                .find_map(|received| received)
+                .expect("Master didn't receive the click");
+        master.watch(slave_addr);
+        let start = Instant::now();
+        while start.elapsed() < timeout * 2 {
+            let _ = master.receive_packet_from_slave(&mut buffer);
+            let _ = slave.receive_packet_from_master(&mut buffer);
+            assert!(master.check_peers().is_empty());
+            assert!(slave.check_peers().is_empty());
+        }
+        drop(slave);
+        let start = Instant::now();
+        let lost = loop {
+            let _ = master.receive_packet_from_slave(&mut buffer);
+            let lost = master.check_peers();
+            if !lost.is_empty() || start.elapsed() > timeout * 2 {
+                break lost;
+            }
+        };
+        assert_eq!(lost, vec![slave_addr]);
+    }
+
+    #[test]
+    fn chat_goes_to_the_master_and_back() {
+        let master_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 47817));
+        let slave_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
+        let master = Messenger::new(Transport::Udp.listen(master_addr), Codec::Json, TIMEOUT);
+        let slave = Messenger::new(
+            Transport::Udp.connect(slave_addr, master_addr),
+            Codec::Json,
+            TIMEOUT,
+        );
+        let mut buffer = MessengerBuffer::new();
+        slave.chat(&mut buffer, "Mind the corner");
+        let (text, slave_addr) =
+            match std::iter::repeat_with(|| master.receive_packet_from_slave(&mut buffer))
+                .take(100)
+                .find_map(|received| received)
+            {
+                Some((MessageFromSlave::Chat(text), addr)) => (text, addr),
+                _ => panic!("Master didn't receive the chat"),
+            };
+        assert_eq!(text, "Mind the corner");
+        let line = chat::ChatLine::new(SessionUserID::new(2), &text).unwrap();
+        master
+            .send_chat_from_master(&mut buffer, slave_addr, line.clone())
+            .unwrap();
+        let received = std::iter::repeat_with(|| slave.receive_packet_from_master(&mut buffer))
+            .take(100)
+            .find_map(|received| received);
+        match received {
+            Some(MessageFromMaster::Chat(received)) => assert_eq!(received, line),
+            _ => panic!("Slave didn't receive the chat"),
+        }
+    }
+
+    const FUZZ_ROUNDS: usize = 2000;
+
+    fn packet<M: serde::Serialize>(codec: Codec, msg: &M) -> Vec<u8> {
+        let mut packet = Vec::new();
+        codec::Envelope::write_message_header(0, 0, codec, true, &mut packet);
+        codec.encode(msg, &mut packet).unwrap();
+        packet
+    }
+
+    /// Feeds random bytes, and valid packets of which some bytes were changed, to `decode`.
+    fn fuzz(packets: &[Vec<u8>], decode: impl Fn(&[u8])) {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+        for _ in 0..FUZZ_ROUNDS {
+            let size = rng.gen_range(0..256);
+            let random: Vec<u8> = (0..size).map(|_| rng.gen()).collect();
+            decode(&random);
+        }
+        for packet in packets {
+            for _ in 0..FUZZ_ROUNDS {
+                let mut mutated = packet.clone();
+                for _ in 0..rng.gen_range(1..4) {
+                    let index = rng.gen_range(0..mutated.len());
+                    mutated[index] = rng.gen();
+                }
+                mutated.truncate(rng.gen_range(0..=mutated.len()));
+                decode(&mutated);
+            }
+        }
+    }
+
+    #[test]
+    fn master_survives_fuzzed_packets() {
+        let packets: Vec<_> = [Codec::Binary, Codec::Json]
+            .into_iter()
+            .flat_map(|codec| {
+                let join = msg::from_slave::JoinRequest {
+                    version: msg::VERSION,
+                    name: "Alice".into(),
+                    codec: codec.into(),
+                    role: Role::Spectator.into(),
+                    token: Some(42),
+                };
+                let click = msg::from_slave::Click {
+                    session: SessionID::new(3).into(),
+                    action: Action::Chord.into(),
+                    coord: Coord { x: 1, y: 2 }.into(),
+                };
+                let chat = msg::from_slave::Chat {
+                    text: "Hello there".into(),
+                };
+                [
+                    packet(codec, &msg::from_slave::MessageSentByClient::Join(join)),
+                    packet(codec, &msg::from_slave::MessageSentByClient::Click(click)),
+                    packet(codec, &msg::from_slave::MessageSentByClient::Chat(chat)),
+                ]
+            })
+            .collect();
+        fuzz(&packets, |datagram| {
+            if let Ok(codec::Envelope::Message { codec, payload, .. }) =
+                codec::Envelope::read(datagram)
+            {
+                if let Ok(msg) = codec.decode::<msg::from_slave::MessageSentByClient>(payload) {
+                    let _: MessageFromSlave = msg.into();
+                }
+                let _ = codec.decode::<msg::from_slave::Handshake>(payload);
+            }
+        });
+    }
+
+    #[test]
+    fn slave_survives_fuzzed_packets() {
+        let packets: Vec<_> = [Codec::Binary, Codec::Json]
+            .into_iter()
+            .flat_map(|codec| {
+                let welcome = msg::from_master::Welcome {
+                    token: Some(42),
+                    mask: Some(vec![true; 12]),
+                    update: full_board_update(4, 3),
+                };
+                let update = full_board_update(4, 3);
+                let rejection = msg::from_master::Rejection {
+                    version: msg::VERSION,
+                    reason: Rejection::GameFull.into(),
+                };
+                let line = chat::ChatLine::new(SessionUserID::new(2), "Hello there").unwrap();
+                [
+                    packet(
+                        codec,
+                        &msg::from_master::MessageSentByMaster::Welcome(welcome),
+                    ),
+                    packet(
+                        codec,
+                        &msg::from_master::MessageSentByMaster::Update(update),
+                    ),
+                    packet(
+                        codec,
+                        &msg::from_master::MessageSentByMaster::Rejection(rejection),
+                    ),
+                    packet(
+                        codec,
+                        &msg::from_master::MessageSentByMaster::Chat(line.into()),
+                    ),
+                ]
+            })
+            .collect();
+        fuzz(&packets, |datagram| {
+            if let Ok(codec::Envelope::Message { codec, payload, .. }) =
+                codec::Envelope::read(datagram)
+            {
+                match codec.decode(payload) {
+                    Ok(msg::from_master::MessageSentByMaster::Update(update)) => {
+                        let _: Result<UpdateFromMaster, _> = (update, None).try_into();
+                    }
+                    Ok(msg::from_master::MessageSentByMaster::Welcome(welcome)) => {
+                        let msg::from_master::Welcome { mask, update, .. } = welcome;
+                        let _: Result<UpdateFromMaster, _> = (update, mask).try_into();
+                    }
+                    Ok(msg::from_master::MessageSentByMaster::Chat(line)) => {
+                        let _: chat::ChatLine = line.into();
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    /// The update which is sent when a slave joins a session of which the first cell has been
+    /// uncovered.
+    fn full_board_update(width: u32, height: u32) -> msg::from_master::Update {
+        let config = game::session::SessionConfig {
+            coords: Coordinations::from_width_and_height(width, height),
+            mines: Mines(width * height / 5),
+            lives: Lives(3),
+            lives_policy: LivesPolicy::Shared,
+            seed: Some(Seed(42)),
+            first_click: FirstClick::Opening,
+            generator: Generator::Random,
+            undo: false,
+        };
+        let uid = SessionUserID::new(1);
+        let mut server = game::server::session::Session::new(&config);
+        let _ = server.uncover(&Coord { x: 0, y: 0 }, uid);
+        let updates = Updates {
+            cells: server.all(),
+            stats: server.stats(),
+        };
+        let client = UpdateFromMasterForClient {
+            coords: server.coords(),
+            updates,
+        };
+        let game = GameUpdateFromMasterForSlave {
+            coords: server.coords(),
+            session: SessionID::new(1),
+            status: server.status(),
+            stats: server.user_stats(),
+            standings: Some(Standings {
+                shown: Team(0),
+                teams: vec![TeamProgress {
+                    team: Team(0),
+                    players: vec![uid],
+                    uncovered: server.cleared().0,
+                    safe: server.cleared().1,
+                    lives_left: server.stats().lives_left,
+                }],
+                winner: None,
+            }),
+        };
+        let names = UserNames(vec![UserName {
+            uid,
+            name: "Alice".into(),
+            connection: Connection::Connected,
+        }]);
+        let namer = UpdateFromMasterForNamer {
+            names,
+            spectators: vec!["Bob".into()],
+        };
+        let slave = UpdateFromMasterForSlave { game, namer };
+        (UpdateFromMaster { client, slave }, Some(uid)).into()
+    }
+
+    fn encoded_size(codec: Codec, update: &msg::from_master::Update) -> usize {
+        let mut buffer = Vec::new();
+        codec.encode(update, &mut buffer).unwrap();
+        buffer.len()
+    }
+
+    #[test]
+    fn mask_which_does_not_fit_is_refused() {
+        let coords =
+            || -> msg::data::Dimensions { Coordinations::from_width_and_height(4, 3).into() };
+        let masked = |mask| -> Result<Coordinations, _> { (coords(), mask).try_into() };
+        assert!(masked(Some(vec![true; 5])).is_err());
+        assert!(masked(Some(vec![true; 12])).is_ok());
+        assert!(masked(None).is_ok());
+        let update = full_board_update(4, 3);
+        let update: Result<UpdateFromMaster, _> = (update, Some(vec![true; 13])).try_into();
+        assert!(update.is_err());
+    }
+
+    #[test]
+    fn update_larger_than_a_datagram_arrives_in_parts() {
+        let master_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 47819));
+        let slave_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
+        let master = Messenger::new(Transport::Udp.listen(master_addr), Codec::Binary, TIMEOUT);
+        let slave = Messenger::new(
+            Transport::Udp.connect(slave_addr, master_addr),
+            Codec::Binary,
+            TIMEOUT,
+        );
+        let mut buffer = MessengerBuffer::new();
+        slave.chat(&mut buffer, "Hello");
+        let (_, slave_addr) =
+            std::iter::repeat_with(|| master.receive_packet_from_slave(&mut buffer))
+                .take(100)
+                .find_map(|received| received)
+                .expect("Master didn't receive the chat");
+        let update = full_board_update(200, 200);
+        assert!(encoded_size(Codec::Binary, &update) > transport::MAX_DATAGRAM_SIZE);
+        let msg = msg::from_master::MessageSentByMaster::Update(update);
+        master
+            .send_reliably(&mut buffer, slave_addr, Codec::Binary, &msg)
+            .unwrap();
+        let start = Instant::now();
+        let received = loop {
+            assert!(start.elapsed() < TIMEOUT, "Slave didn't receive the update");
+            if let Some((received, _)) = slave.receive_message_from_master(&mut buffer) {
+                break received;
+            }
+            let _ = master.receive_packet_from_slave(&mut buffer);
+            master.retransmit();
+        };
+        match received {
+            msg::from_master::MessageSentByMaster::Update(update) => {
+                let update: UpdateFromMaster = (update, None).try_into().unwrap();
+                assert_eq!(update.client.coords.size(), 200 * 200);
+            }
+            _ => panic!("Slave received something else"),
+        }
+        // every part got acknowledged
+        for _ in 0..100 {
+            let _ = master.receive_packet_from_slave(&mut buffer);
+        }
+        std::thread::sleep(reliable::RETRANSMIT_TIMEOUT);
+        assert!(master.outbox.lock().unwrap().due(Instant::now()).is_empty());
+    }
+
+    #[test]
+    fn binary_update_is_smaller_than_json() {
+        let update = full_board_update(20, 20);
+        assert!(encoded_size(Codec::Binary, &update) < encoded_size(Codec::Json, &update));
+    }
+
+    /// Run with `cargo test --release codec_benchmark -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn codec_benchmark() {
+        const RUNS: u32 = 20;
+        let update = full_board_update(200, 200);
+        for codec in [Codec::Binary, Codec::Json] {
+            let mut buffer = Vec::new();
+            let start = Instant::now();
+            for _ in 0..RUNS {
+                buffer.clear();
+                codec.encode(&update, &mut buffer).unwrap();
+            }
+            let encoding = start.elapsed() / RUNS;
+            let start = Instant::now();
+            for _ in 0..RUNS {
+                let _: msg::from_master::Update = codec.decode(&buffer).unwrap();
+            }
+            let decoding = start.elapsed() / RUNS;
+            println!(
+                "{codec:?}: {} bytes, encoding takes {encoding:?}, decoding takes {decoding:?}",
+                buffer.len()
+            );
+        }
+    }
+}
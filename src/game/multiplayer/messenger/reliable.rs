@@ -0,0 +1,330 @@
+//! Reliable delivery on top of UDP.
+//! Every message carries a sequence number per peer, which the receiver acknowledges.
+//! Messages which aren't acknowledged in time are sent again, and the receiver passes on every
+//! message exactly once, in the order in which it was sent.
+//! The sequence numbers of a peer belong to a stream, which is picked at random. A peer which
+//! starts over, for example because it was restarted, starts a new stream.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+pub const RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(100);
+/// After this many attempts, the peer is assumed to be gone.
+pub const MAX_ATTEMPTS: u32 = 50;
+/// How far ahead of the next expected message a received message may be. Messages further ahead
+/// are dropped, so a peer can't make the receiver keep an unlimited number of them.
+pub const RECEIVE_WINDOW: Seq = 1024;
+
+pub type Seq = u64;
+pub type Stream = u32;
+
+struct Unacked {
+    datagram: Vec<u8>,
+    sent: Instant,
+    attempts: u32,
+}
+
+/// Keeps the messages that have been sent, until they are acknowledged.
+#[derive(Default)]
+pub struct Outbox {
+    next_seq: HashMap<SocketAddr, (Stream, Seq)>,
+    unacked: BTreeMap<(SocketAddr, Seq), Unacked>,
+}
+
+impl Outbox {
+    /// Hands out the stream and sequence number of the next message to `addr`.
+    pub fn next_seq(&mut self, addr: SocketAddr) -> (Stream, Seq) {
+        let (stream, seq) = self
+            .next_seq
+            .entry(addr)
+            .or_insert_with(|| (rand::random(), 0));
+        let next = *seq;
+        *seq += 1;
+        (*stream, next)
+    }
+
+    /// Whether or not every message has been acknowledged.
+    pub fn is_empty(&self) -> bool {
+        self.unacked.is_empty()
+    }
+
+    pub fn keep(&mut self, addr: SocketAddr, seq: Seq, datagram: Vec<u8>, now: Instant) {
+        let unacked = Unacked {
+            datagram,
+            sent: now,
+            attempts: 1,
+        };
+        self.unacked.insert((addr, seq), unacked);
+    }
+
+    /// Acknowledgements of a stream which was forgotten are ignored.
+    pub fn ack(&mut self, addr: SocketAddr, stream: Stream, seq: Seq) {
+        if self.next_seq.get(&addr).map(|&(current, _)| current) == Some(stream) {
+            self.unacked.remove(&(addr, seq));
+        }
+    }
+
+    /// Drops everything which was sent to `addr`, and continues with the next stream.
+    fn restart(&mut self, addr: SocketAddr) {
+        self.unacked
+            .retain(|(unacked_addr, _seq), _| *unacked_addr != addr);
+        if let Some((stream, seq)) = self.next_seq.get_mut(&addr) {
+            *stream = stream.wrapping_add(1);
+            *seq = 0;
+        }
+    }
+
+    /// Drops everything which was sent to `addr`, after which a new stream starts.
+    pub fn forget(&mut self, addr: SocketAddr) {
+        self.next_seq.remove(&addr);
+        self.unacked
+            .retain(|(unacked_addr, _seq), _| *unacked_addr != addr);
+    }
+
+    /// Returns the messages which should be sent again. When a message has been sent too often,
+    /// everything which was sent to its peer is dropped and a new stream starts, as the peer
+    /// would otherwise keep waiting for the message that never arrives.
+    pub fn due(&mut self, now: Instant) -> Vec<(SocketAddr, Vec<u8>)> {
+        let given_up: Vec<SocketAddr> = self
+            .unacked
+            .iter()
+            .filter(|(_, unacked)| unacked.attempts >= MAX_ATTEMPTS)
+            .map(|(&(addr, seq), _)| {
+                println!("Giving up on message {seq} to {addr}");
+                addr
+            })
+            .collect();
+        for addr in given_up {
+            self.restart(addr);
+        }
+        self.unacked
+            .iter_mut()
+            .filter(|(_, unacked)| {
+                now.saturating_duration_since(unacked.sent) >= RETRANSMIT_TIMEOUT
+            })
+            .map(|((addr, _seq), unacked)| {
+                unacked.sent = now;
+                unacked.attempts += 1;
+                (*addr, unacked.datagram.clone())
+            })
+            .collect()
+    }
+}
+
+struct Peer<M> {
+    stream: Stream,
+    next_seq: Seq,
+    early: BTreeMap<Seq, M>,
+}
+
+impl<M> Peer<M> {
+    fn new(stream: Stream) -> Self {
+        let next_seq = 0;
+        let early = BTreeMap::new();
+        Self {
+            stream,
+            next_seq,
+            early,
+        }
+    }
+}
+
+/// What became of a received message.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Received {
+    /// It was received before, and is dropped.
+    Again,
+    /// It is too far ahead of the next expected message, and is dropped.
+    TooEarly,
+    New,
+    /// It is the first message from a peer that started over.
+    Restarted,
+}
+
+/// Puts received messages back in order, and drops the ones that were received before.
+pub struct Inbox<M> {
+    peers: HashMap<SocketAddr, Peer<M>>,
+    ready: VecDeque<(M, SocketAddr)>,
+}
+
+impl<M> Default for Inbox<M> {
+    fn default() -> Self {
+        let peers = HashMap::new();
+        let ready = VecDeque::new();
+        Self { peers, ready }
+    }
+}
+
+impl<M> Inbox<M> {
+    pub fn accept(&mut self, addr: SocketAddr, stream: Stream, seq: Seq, message: M) -> Received {
+        let next_seq = match self.peers.get(&addr) {
+            Some(peer) if peer.stream == stream => peer.next_seq,
+            _ => 0,
+        };
+        if seq.saturating_sub(next_seq) >= RECEIVE_WINDOW {
+            return Received::TooEarly;
+        }
+        let restarted = self
+            .peers
+            .get(&addr)
+            .is_some_and(|peer| peer.stream != stream);
+        if restarted {
+            // whatever was received from before is still passed on
+            self.peers.remove(&addr);
+        }
+        let peer = self.peers.entry(addr).or_insert_with(|| Peer::new(stream));
+        if seq < peer.next_seq || peer.early.contains_key(&seq) {
+            return Received::Again;
+        }
+        peer.early.insert(seq, message);
+        while let Some(message) = peer.early.remove(&peer.next_seq) {
+            self.ready.push_back((message, addr));
+            peer.next_seq += 1;
+        }
+        match restarted {
+            true => Received::Restarted,
+            false => Received::New,
+        }
+    }
+
+    pub fn next(&mut self) -> Option<(M, SocketAddr)> {
+        self.ready.pop_front()
+    }
+
+    /// Drops everything which was received from `addr`, after which any stream is accepted.
+    pub fn forget(&mut self, addr: SocketAddr) {
+        self.peers.remove(&addr);
+        self.ready
+            .retain(|(_message, ready_addr)| *ready_addr != addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn inbox_passes_on_messages_once_and_in_order() {
+        let mut inbox = Inbox::default();
+        assert_eq!(inbox.accept(addr(1), 7, 1, 1), Received::New);
+        assert_eq!(inbox.next(), None);
+        assert_eq!(inbox.accept(addr(1), 7, 0, 0), Received::New);
+        assert_eq!(inbox.accept(addr(1), 7, 0, 0), Received::Again);
+        assert_eq!(inbox.accept(addr(1), 7, 1, 1), Received::Again);
+        assert_eq!(inbox.next(), Some((0, addr(1))));
+        assert_eq!(inbox.next(), Some((1, addr(1))));
+        assert_eq!(inbox.next(), None);
+    }
+
+    #[test]
+    fn inbox_drops_messages_far_ahead() {
+        let mut inbox = Inbox::default();
+        assert_eq!(
+            inbox.accept(addr(1), 7, RECEIVE_WINDOW, 1),
+            Received::TooEarly
+        );
+        assert_eq!(inbox.accept(addr(1), 7, Seq::MAX, 1), Received::TooEarly);
+        assert_eq!(
+            inbox.accept(addr(1), 7, RECEIVE_WINDOW - 1, 1),
+            Received::New
+        );
+        assert_eq!(inbox.accept(addr(1), 7, 0, 0), Received::New);
+        assert_eq!(inbox.next(), Some((0, addr(1))));
+        assert_eq!(inbox.next(), None);
+    }
+
+    #[test]
+    fn inbox_keeps_peers_apart() {
+        let mut inbox = Inbox::default();
+        assert_eq!(inbox.accept(addr(1), 7, 0, 1), Received::New);
+        assert_eq!(inbox.accept(addr(2), 7, 0, 2), Received::New);
+        assert_eq!(inbox.next(), Some((1, addr(1))));
+        assert_eq!(inbox.next(), Some((2, addr(2))));
+    }
+
+    #[test]
+    fn forgotten_peers_start_over() {
+        let mut inbox = Inbox::default();
+        assert_eq!(inbox.accept(addr(1), 7, 0, 0), Received::New);
+        inbox.forget(addr(1));
+        assert_eq!(inbox.next(), None);
+        assert_eq!(inbox.accept(addr(1), 7, 0, 0), Received::New);
+        let mut outbox = Outbox::default();
+        let now = Instant::now();
+        let (stream, seq) = outbox.next_seq(addr(1));
+        outbox.keep(addr(1), seq, vec![42], now);
+        outbox.forget(addr(1));
+        assert_eq!(outbox.next_seq(addr(1)).1, seq);
+        assert!(outbox.due(now + RETRANSMIT_TIMEOUT).is_empty());
+        outbox.keep(addr(1), seq, vec![42], now);
+        outbox.ack(addr(1), stream, seq);
+        assert_eq!(outbox.due(now + RETRANSMIT_TIMEOUT).len(), 1);
+    }
+
+    #[test]
+    fn inbox_notices_peers_that_start_over() {
+        let mut inbox = Inbox::default();
+        assert_eq!(inbox.accept(addr(1), 7, 0, 0), Received::New);
+        assert_eq!(inbox.accept(addr(1), 7, 2, 2), Received::New);
+        assert_eq!(inbox.accept(addr(1), 8, 0, 10), Received::Restarted);
+        assert_eq!(inbox.accept(addr(1), 8, 1, 11), Received::New);
+        assert_eq!(inbox.next(), Some((0, addr(1))));
+        assert_eq!(inbox.next(), Some((10, addr(1))));
+        assert_eq!(inbox.next(), Some((11, addr(1))));
+        assert_eq!(inbox.next(), None);
+    }
+
+    #[test]
+    fn outbox_retransmits_until_acknowledged() {
+        let mut outbox = Outbox::default();
+        let now = Instant::now();
+        let (stream, seq) = outbox.next_seq(addr(1));
+        assert_eq!(outbox.next_seq(addr(1)), (stream, seq + 1));
+        assert_eq!(outbox.next_seq(addr(2)).1, seq);
+        outbox.keep(addr(1), seq, vec![42], now);
+        assert!(outbox.due(now).is_empty());
+        let later = now + RETRANSMIT_TIMEOUT;
+        assert_eq!(outbox.due(later), vec![(addr(1), vec![42])]);
+        assert!(outbox.due(later).is_empty());
+        outbox.ack(addr(1), stream, seq);
+        assert!(outbox.due(later + RETRANSMIT_TIMEOUT).is_empty());
+    }
+
+    #[test]
+    fn outbox_gives_up_eventually() {
+        let mut outbox = Outbox::default();
+        let mut now = Instant::now();
+        outbox.keep(addr(1), 0, vec![42], now);
+        for _ in 1..MAX_ATTEMPTS {
+            now += RETRANSMIT_TIMEOUT;
+            assert_eq!(outbox.due(now).len(), 1);
+        }
+        now += RETRANSMIT_TIMEOUT;
+        assert!(outbox.due(now).is_empty());
+    }
+
+    #[test]
+    fn peer_gets_a_new_stream_after_giving_up() {
+        let mut outbox = Outbox::default();
+        let mut inbox = Inbox::default();
+        let mut now = Instant::now();
+        let (stream, lost) = outbox.next_seq(addr(1));
+        outbox.keep(addr(1), lost, vec![42], now);
+        let (_, seq) = outbox.next_seq(addr(1));
+        assert_eq!(inbox.accept(addr(2), stream, seq, 1), Received::New);
+        for _ in 0..MAX_ATTEMPTS {
+            now += RETRANSMIT_TIMEOUT;
+            let _ = outbox.due(now);
+        }
+        let (stream, seq) = outbox.next_seq(addr(1));
+        assert_eq!(seq, 0);
+        assert_eq!(inbox.accept(addr(2), stream, seq, 2), Received::Restarted);
+        assert_eq!(inbox.next(), Some((2, addr(2))));
+        assert_eq!(inbox.next(), None);
+    }
+}
@@ -0,0 +1,488 @@
+//! The ways in which messages can travel between master and slaves.
+//! UDP sends every message as a datagram. TCP sends every message as a frame, which is the
+//! length of the message as a big-endian `u32` followed by the message itself.
+
+use super::*;
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+
+/// Carries messages between master and slaves.
+pub trait Channel: Send + Sync {
+    /// Sends a whole message to `addr`.
+    fn send_to(&self, message: &[u8], addr: SocketAddr) -> io::Result<usize>;
+    /// Waits a short while for a message, which is copied into `buffer`.
+    fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)>;
+    /// The master, when connected to it.
+    fn peer_addr(&self) -> io::Result<SocketAddr>;
+    /// Whether or not messages arrive without having to acknowledge them.
+    fn is_reliable(&self) -> bool;
+    /// The size of the largest message which can be sent at once.
+    fn max_message_size(&self) -> usize;
+}
+
+/// The transport used between master and slaves.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Transport {
+    #[default]
+    Udp,
+    Tcp,
+}
+
+impl Transport {
+    /// Sets up the transport of the master, on which slaves can join.
+    pub fn listen(&self, master: SocketAddr) -> Box<dyn Channel> {
+        match self {
+            Transport::Udp => {
+                let socket = UdpSocket::bind(master).expect("Failed to bind master socket");
+                socket
+                    .set_read_timeout(Some(SOCKET_TIMEOUT))
+                    .expect("Failed to set socket read timeout");
+                socket
+                    .set_write_timeout(Some(SOCKET_TIMEOUT))
+                    .expect("Failed to set socket write timeout");
+                Box::new(socket)
+            }
+            Transport::Tcp => {
+                let listener = TcpListener::bind(master).expect("Failed to bind master socket");
+                listener
+                    .set_nonblocking(true)
+                    .expect("Failed to make master socket non-blocking");
+                Box::new(TcpServer::new(listener))
+            }
+        }
+    }
+
+    /// Sets up the transport of a slave at `slave`, towards the master at `master`.
+    pub fn connect(&self, slave: SocketAddr, master: SocketAddr) -> Box<dyn Channel> {
+        match self {
+            Transport::Udp => {
+                let socket = UdpSocket::bind(slave).expect("Failed to bind local socket");
+                socket
+                    .connect(master)
+                    .expect("Failed to connect to master server");
+                socket
+                    .set_read_timeout(Some(SOCKET_TIMEOUT))
+                    .expect("Failed to set socket read timeout");
+                socket
+                    .set_write_timeout(Some(SOCKET_TIMEOUT))
+                    .expect("Failed to set socket write timeout");
+                Box::new(socket)
+            }
+            Transport::Tcp => {
+                // the operating system picks the local port of a tcp connection
+                let stream = loop {
+                    println!("Connecting to {master}");
+                    match TcpStream::connect(master) {
+                        Ok(stream) => break stream,
+                        Err(_) => std::thread::sleep(RECONNECT_DELAY),
+                    }
+                };
+                let connection = Connection::new(stream).expect("Failed to set up connection");
+                Box::new(TcpClient {
+                    master,
+                    connection: Mutex::new(Some(connection)),
+                })
+            }
+        }
+    }
+}
+
+const RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+const FRAME_HEADER_SIZE: usize = std::mem::size_of::<u32>();
+/// The most that may wait to be sent over a connection. A peer which doesn't read fast enough to
+/// stay below it is dropped, rather than making everyone else wait.
+const MAX_OUTGOING_SIZE: usize = 4 * MAX_PACKET_SIZE;
+
+/// Well below the 64 KiB which UDP can carry at most, as not every system sends datagrams that
+/// large.
+pub const MAX_DATAGRAM_SIZE: usize = 1 << 13;
+
+impl Channel for UdpSocket {
+    fn send_to(&self, message: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        match UdpSocket::peer_addr(self) {
+            Ok(_) => self.send(message),
+            Err(_) => UdpSocket::send_to(self, message, addr),
+        }
+    }
+
+    fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        UdpSocket::recv_from(self, buffer)
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        UdpSocket::peer_addr(self)
+    }
+
+    fn is_reliable(&self) -> bool {
+        false
+    }
+
+    fn max_message_size(&self) -> usize {
+        MAX_DATAGRAM_SIZE
+    }
+}
+
+/// A tcp stream of frames.
+struct Connection {
+    stream: TcpStream,
+    received: Vec<u8>,
+    /// The frames which the stream didn't take yet.
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        let received = Vec::new();
+        let outgoing = Vec::new();
+        Ok(Self {
+            stream,
+            received,
+            outgoing,
+        })
+    }
+
+    /// Queues the message, and sends as much as the stream takes without blocking.
+    /// Fails when the peer is too far behind.
+    fn send(&mut self, message: &[u8]) -> io::Result<usize> {
+        let size: u32 = message
+            .len()
+            .try_into()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Message too large"))?;
+        if self.outgoing.len() + FRAME_HEADER_SIZE + message.len() > MAX_OUTGOING_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Peer doesn't keep up",
+            ));
+        }
+        self.outgoing.extend_from_slice(&size.to_be_bytes());
+        self.outgoing.extend_from_slice(message);
+        self.flush()?;
+        Ok(message.len())
+    }
+
+    /// Sends as much of the queued frames as the stream takes without blocking.
+    fn flush(&mut self) -> io::Result<()> {
+        let mut written = 0;
+        while written < self.outgoing.len() {
+            match self.stream.write(&self.outgoing[written..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => written += n,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+        self.outgoing.drain(..written);
+        Ok(())
+    }
+
+    /// Reads whatever has arrived, and returns the first complete frame.
+    /// Fails when the connection is closed.
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut chunk = [0; 4096];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => self.received.extend_from_slice(&chunk[..n]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+        take_frame(&mut self.received)
+    }
+}
+
+/// Removes the first complete frame from `received`, and returns its message.
+/// Fails when the frame is too large.
+fn take_frame(received: &mut Vec<u8>) -> io::Result<Option<Vec<u8>>> {
+    let header: [u8; FRAME_HEADER_SIZE] = match received.get(..FRAME_HEADER_SIZE) {
+        Some(header) => header.try_into().unwrap(),
+        None => return Ok(None),
+    };
+    let size = u32::from_be_bytes(header) as usize;
+    // rather than waiting for a frame which will never fit
+    if size > MAX_PACKET_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Frame too large",
+        ));
+    }
+    let frame = match received.get(FRAME_HEADER_SIZE..FRAME_HEADER_SIZE + size) {
+        Some(frame) => frame.to_vec(),
+        None => return Ok(None),
+    };
+    received.drain(..FRAME_HEADER_SIZE + size);
+    Ok(Some(frame))
+}
+
+fn copy_frame(frame: Vec<u8>, buffer: &mut [u8]) -> io::Result<usize> {
+    let target = buffer
+        .get_mut(..frame.len())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Frame too large"))?;
+    target.copy_from_slice(&frame);
+    Ok(frame.len())
+}
+
+struct TcpServer {
+    listener: TcpListener,
+    connections: Mutex<HashMap<SocketAddr, Connection>>,
+    /// Frames which were received, at most one of every connection at a time, so that a peer
+    /// which keeps sending doesn't keep the others waiting.
+    ready: Mutex<VecDeque<(Vec<u8>, SocketAddr)>>,
+}
+
+impl TcpServer {
+    fn new(listener: TcpListener) -> Self {
+        let connections = Default::default();
+        let ready = Default::default();
+        Self {
+            listener,
+            connections,
+            ready,
+        }
+    }
+}
+
+impl Channel for TcpServer {
+    fn send_to(&self, message: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        let mut connections = self.connections.lock().expect("Failed to lock connections");
+        let connection = connections
+            .get_mut(&addr)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+        let sent = connection.send(message);
+        if let Err(err) = &sent {
+            println!("Lost the connection with {addr}: {err}");
+            connections.remove(&addr);
+        }
+        sent
+    }
+
+    fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let mut connections = self.connections.lock().expect("Failed to lock connections");
+        while let Ok((stream, addr)) = self.listener.accept() {
+            match Connection::new(stream) {
+                Ok(connection) => {
+                    println!("Accepted a connection from {addr}");
+                    connections.insert(addr, connection);
+                }
+                Err(err) => println!("Failed to accept a connection from {addr}: {err}"),
+            }
+        }
+        let mut ready = self.ready.lock().expect("Failed to lock received frames");
+        if ready.is_empty() {
+            let mut closed = Vec::new();
+            for (addr, connection) in connections.iter_mut() {
+                if connection.flush().is_err() {
+                    closed.push(*addr);
+                    continue;
+                }
+                match connection.receive() {
+                    Ok(Some(frame)) => ready.push_back((frame, *addr)),
+                    Ok(None) => {}
+                    Err(_) => closed.push(*addr),
+                }
+            }
+            for addr in closed {
+                println!("Lost the connection with {addr}");
+                connections.remove(&addr);
+            }
+        }
+        let received = ready.pop_front();
+        drop(ready);
+        drop(connections);
+        match received {
+            Some((frame, addr)) => Ok((copy_frame(frame, buffer)?, addr)),
+            None => {
+                std::thread::sleep(SOCKET_TIMEOUT);
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+        }
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        Err(io::ErrorKind::NotConnected.into())
+    }
+
+    fn is_reliable(&self) -> bool {
+        true
+    }
+
+    fn max_message_size(&self) -> usize {
+        MAX_PACKET_SIZE
+    }
+}
+
+struct TcpClient {
+    master: SocketAddr,
+    /// None once the connection is closed.
+    connection: Mutex<Option<Connection>>,
+}
+
+impl Channel for TcpClient {
+    fn send_to(&self, message: &[u8], _addr: SocketAddr) -> io::Result<usize> {
+        self.connection
+            .lock()
+            .expect("Failed to lock connection")
+            .as_mut()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?
+            .send(message)
+    }
+
+    fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let mut connection = self.connection.lock().expect("Failed to lock connection");
+        let received = match connection.as_mut() {
+            Some(connection) => connection.flush().and_then(|()| connection.receive()),
+            None => Err(io::ErrorKind::NotConnected.into()),
+        };
+        let frame = match received {
+            Ok(frame) => frame,
+            Err(err) => {
+                // the master is reported lost once it hasn't been heard from in a while
+                if connection.take().is_some() {
+                    println!("Lost the connection with the master: {err}");
+                }
+                drop(connection);
+                std::thread::sleep(SOCKET_TIMEOUT);
+                return Err(err);
+            }
+        };
+        drop(connection);
+        match frame {
+            Some(frame) => Ok((copy_frame(frame, buffer)?, self.master)),
+            None => {
+                std::thread::sleep(SOCKET_TIMEOUT);
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+        }
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.master)
+    }
+
+    fn is_reliable(&self) -> bool {
+        true
+    }
+
+    fn max_message_size(&self) -> usize {
+        MAX_PACKET_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_taken_once_complete() {
+        let mut received = vec![0, 0, 0, 3, b'a', b'b'];
+        assert_eq!(take_frame(&mut received).unwrap(), None);
+        received.extend_from_slice(&[b'c', 0, 0]);
+        assert_eq!(take_frame(&mut received).unwrap(), Some(b"abc".to_vec()));
+        assert_eq!(take_frame(&mut received).unwrap(), None);
+        received.extend_from_slice(&[0, 0]);
+        assert_eq!(take_frame(&mut received).unwrap(), Some(Vec::new()));
+        assert!(received.is_empty());
+    }
+
+    #[test]
+    fn frames_which_are_too_large_are_refused() {
+        let mut received = u32::MAX.to_be_bytes().to_vec();
+        assert!(take_frame(&mut received).is_err());
+    }
+
+    #[test]
+    fn tcp_carries_messages_both_ways() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let master = TcpServer::new(listener);
+        let slave = Transport::Tcp.connect("127.0.0.1:0".parse().unwrap(), addr);
+        let mut buffer = [0; 16];
+        slave.send_to(b"join", addr).unwrap();
+        let (size, from) = loop {
+            if let Ok(received) = master.recv_from(&mut buffer) {
+                break received;
+            }
+        };
+        assert_eq!(&buffer[..size], b"join");
+        master.send_to(b"welcome", from).unwrap();
+        let (size, _) = loop {
+            if let Ok(received) = slave.recv_from(&mut buffer) {
+                break received;
+            }
+        };
+        assert_eq!(&buffer[..size], b"welcome");
+    }
+
+    #[test]
+    fn master_takes_turns_between_slaves() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let master = TcpServer::new(listener);
+        let mut chatty = TcpStream::connect(addr).unwrap();
+        let mut quiet: Vec<TcpStream> = (0..3).map(|_| TcpStream::connect(addr).unwrap()).collect();
+        for _ in 0..100 {
+            chatty.write_all(&[0, 0, 0, 1, b'a']).unwrap();
+        }
+        for quiet in &mut quiet {
+            quiet.write_all(&[0, 0, 0, 1, b'b']).unwrap();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let mut buffer = [0; 16];
+        let mut received = Vec::new();
+        while received.len() < 4 {
+            if master.recv_from(&mut buffer).is_ok() {
+                received.push(buffer[0]);
+            }
+        }
+        assert_eq!(received.iter().filter(|&&frame| frame == b'b').count(), 3);
+    }
+
+    #[test]
+    fn slave_waits_once_the_master_is_gone() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let slave = Transport::Tcp.connect("127.0.0.1:0".parse().unwrap(), addr);
+        drop(listener.accept().unwrap());
+        let mut buffer = [0; 16];
+        let closed = |result: io::Result<(usize, SocketAddr)>| {
+            result.is_err_and(|err| err.kind() != io::ErrorKind::WouldBlock)
+        };
+        while !closed(slave.recv_from(&mut buffer)) {}
+        let start = std::time::Instant::now();
+        for _ in 0..3 {
+            let err = slave.recv_from(&mut buffer).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotConnected);
+        }
+        assert!(start.elapsed() >= 3 * SOCKET_TIMEOUT);
+        assert!(slave.send_to(b"click", addr).is_err());
+    }
+
+    #[test]
+    fn master_drops_slaves_which_stop_reading() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let master = TcpServer::new(listener);
+        // connected, but never reads
+        let _slave = TcpStream::connect(addr).unwrap();
+        let mut buffer = [0; 16];
+        let from = loop {
+            let _ = master.recv_from(&mut buffer);
+            if let Some(&from) = master.connections.lock().unwrap().keys().next() {
+                break from;
+            }
+        };
+        let message = vec![0; MAX_PACKET_SIZE];
+        let start = std::time::Instant::now();
+        while master.send_to(&message, from).is_ok() {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+        }
+        assert!(master.connections.lock().unwrap().is_empty());
+    }
+}
@@ -7,4 +7,7 @@ pub mod slave;
 use super::*;
 use crate::game;
 use messenger::{Messenger, MessengerBuffer, MessengerThread};
+
+pub use messenger::{Codec, Transport};
+pub use types::RejoinToken;
 use types::*;
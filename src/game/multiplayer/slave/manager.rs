@@ -5,41 +5,76 @@ use std::sync;
 pub struct Slave(pub std::net::SocketAddr);
 pub struct Master(pub std::net::SocketAddr);
 
+/// How to take part in the session of the master.
+#[derive(Clone, Copy, Debug)]
+pub enum JoinAs {
+    /// With the token of the place to take back, if any.
+    Player(Option<RejoinToken>),
+    Spectator,
+}
+
 pub struct Manager {
     name: String,
     slave: std::net::SocketAddr,
     master: std::net::SocketAddr,
+    transport: Transport,
+    codec: Codec,
+    timeout: std::time::Duration,
+    /// Remembers the token of the place to take back when joining again.
+    join_as: sync::Mutex<JoinAs>,
 }
 
 impl Manager {
-    pub fn new(name: String, slave: Slave, master: Master) -> Self {
+    pub fn new(
+        name: String,
+        slave: Slave,
+        master: Master,
+        transport: Transport,
+        codec: Codec,
+        timeout: std::time::Duration,
+        join_as: JoinAs,
+    ) -> Self {
         let Slave(slave) = slave;
         let Master(master) = master;
+        let join_as = sync::Mutex::new(join_as);
         Self {
             name,
             slave,
             master,
+            transport,
+            codec,
+            timeout,
+            join_as,
         }
     }
 }
 
-impl game::session::SessionManager for Manager {
-    type Session = MultiplayerSession;
-
-    fn request_new_session(&self) -> Self::Session {
-        let remote = std::net::UdpSocket::bind(self.slave).expect("Failed to bind local socket");
-        remote
-            .connect(self.master)
-            .expect("Failed to connect to master server");
-        let messenger = sync::Arc::new(Messenger::new(remote));
+impl Manager {
+    /// Joins the session of the master, unless the master turns the request down.
+    pub fn join(&self) -> Result<MultiplayerSession, Rejection> {
+        let channel = self.transport.connect(self.slave, self.master);
+        let messenger = sync::Arc::new(Messenger::new(channel, self.codec, self.timeout));
         let thread_messenger = sync::Arc::downgrade(&messenger);
         let mut buffer = MessengerBuffer::new();
-        let UpdateFromMaster {
-            client: client_initial,
-            slave: slave_initial,
-        } = messenger.request_to_join(&mut buffer, &self.name);
+        let mut join_as = self.join_as.lock().expect("Failed to lock join role");
+        let (role, token) = match *join_as {
+            JoinAs::Player(token) => (Role::Player, token),
+            JoinAs::Spectator => (Role::Spectator, None),
+        };
+        let (
+            UpdateFromMaster {
+                client: client_initial,
+                slave: slave_initial,
+            },
+            rejoin,
+        ) = messenger.request_to_join(&mut buffer, &self.name, role, token)?;
+        if let Some(rejoin) = rejoin {
+            println!("Joined, to take back this place later use --rejoin {rejoin}");
+            *join_as = JoinAs::Player(Some(rejoin));
+        }
+        drop(join_as);
         let client = client::create_client_session_from_updates_from_master(client_initial);
-        let server = server::Server::new(messenger, buffer, slave_initial);
+        let server = server::Server::new(messenger, buffer, slave_initial, role);
         let session = {
             let session = session::Session::new(client, server);
             let session = sync::Mutex::new(session);
@@ -50,10 +85,22 @@ impl game::session::SessionManager for Manager {
             thread_messenger,
             master_listener,
         );
-        MultiplayerSession {
+        Ok(MultiplayerSession {
             session,
             _messenger_thread,
-        }
+        })
+    }
+}
+
+impl game::session::SessionManager for Manager {
+    type Session = MultiplayerSession;
+
+    fn request_new_session(&self) -> Self::Session {
+        self.join().unwrap_or_else(|rejection| {
+            // being turned down is no bug, so it ends without a backtrace
+            eprintln!("Master rejected the join request: {rejection}");
+            std::process::exit(1)
+        })
     }
 }
 
@@ -88,4 +135,24 @@ impl MasterListener for MyMasterListener {
                 .on_updates_from_master_to_slave(update);
         }
     }
+
+    fn on_chat_from_master(&mut self, line: chat::ChatLine) {
+        let Self(session) = self;
+        if let Some(session) = session.upgrade() {
+            session
+                .lock()
+                .expect("Failed to lock multiplayer session")
+                .on_chat_from_master(line);
+        }
+    }
+
+    fn on_master_lost(&mut self) {
+        let Self(session) = self;
+        if let Some(session) = session.upgrade() {
+            session
+                .lock()
+                .expect("Failed to lock multiplayer session")
+                .on_master_lost();
+        }
+    }
 }
@@ -32,6 +32,10 @@ fn thread_fn<L>(
     ) {
         let messenger = &messenger;
         thread_cycle(messenger, &mut buffer, &mut listener);
+        messenger.retransmit();
+        if !messenger.check_peers().is_empty() {
+            listener.on_master_lost();
+        }
     }
 }
 
@@ -39,10 +43,11 @@ fn thread_cycle<L>(messenger: &Messenger, buffer: &mut MessengerBuffer, listener
 where
     L: MasterListener,
 {
-    let update = match messenger.receive_packet_from_master(buffer) {
-        Some(update) => update,
-        None => return,
-    };
-
-    listener.on_updates_from_master_to_slave(update);
+    match messenger.receive_packet_from_master(buffer) {
+        Some(MessageFromMaster::Update(update)) => {
+            listener.on_updates_from_master_to_slave(*update)
+        }
+        Some(MessageFromMaster::Chat(line)) => listener.on_chat_from_master(line),
+        None => {}
+    }
 }
@@ -3,6 +3,8 @@ use super::*;
 pub struct Server {
     core: Core,
     namer: Namer,
+    role: Role,
+    spectator: Spectator,
 }
 
 impl Server {
@@ -10,6 +12,7 @@ impl Server {
         messenger: std::sync::Arc<Messenger>,
         buffer: MessengerBuffer,
         initial: UpdateFromMasterForSlave,
+        role: Role,
     ) -> Self {
         let UpdateFromMasterForSlave {
             game: initial_game,
@@ -23,7 +26,12 @@ impl Server {
             buffer,
             latest: initial_game,
         };
-        Self { core, namer }
+        Self {
+            core,
+            namer,
+            role,
+            spectator: Spectator,
+        }
     }
 
     pub fn status(&self) -> Status {
@@ -31,15 +39,31 @@ impl Server {
     }
 
     pub fn coords(&self) -> Coordinations {
-        self.core.latest.coords
+        self.core.latest.coords.clone()
     }
 
     pub fn user_stats(&self) -> UserStats {
         self.core.latest.stats.clone()
     }
 
+    pub fn players(&self) -> Vec<(SessionUserID, Connection)> {
+        self.namer.latest.names.players()
+    }
+
+    pub fn standings(&self) -> Option<Standings> {
+        self.core.latest.standings.clone()
+    }
+
+    pub fn spectators(&self) -> Vec<String> {
+        self.namer.latest.spectators.clone()
+    }
+
     pub fn servitors(&mut self) -> (&mut dyn LocalPlayerListener, &dyn game::session::Namer) {
-        (&mut self.core, &self.namer)
+        let local_player_listener: &mut dyn LocalPlayerListener = match self.role {
+            Role::Player => &mut self.core,
+            Role::Spectator => &mut self.spectator,
+        };
+        (local_player_listener, &self.namer)
     }
 
     pub fn on_updates_from_master(&mut self, update: UpdateFromMasterForSlave) {
@@ -67,6 +91,29 @@ impl LocalPlayerListener for Core {
         self.messenger
             .right_click(&mut self.buffer, sessionid, coord)
     }
+
+    fn on_chord(&mut self, coord: &Coord) {
+        let sessionid = self.latest.session;
+        self.messenger.chord(&mut self.buffer, sessionid, coord)
+    }
+
+    /// Only the master can undo moves.
+    fn on_undo(&mut self) {}
+
+    fn on_chat(&mut self, text: &str) {
+        self.messenger.chat(&mut self.buffer, text)
+    }
+}
+
+/// Only watches, so clicks and messages go nowhere.
+struct Spectator;
+
+impl LocalPlayerListener for Spectator {
+    fn on_left_click(&mut self, _coord: &Coord) {}
+    fn on_right_click(&mut self, _coord: &Coord) {}
+    fn on_chord(&mut self, _coord: &Coord) {}
+    fn on_undo(&mut self) {}
+    fn on_chat(&mut self, _text: &str) {}
 }
 
 struct Namer {
@@ -79,7 +126,7 @@ impl game::session::Namer for Namer {
         let UserNames(usernames) = &self.latest.names;
         usernames
             .iter()
-            .find_map(|UserName { uid, name }| (id == *uid).then_some(name))
+            .find_map(|UserName { uid, name, .. }| (id == *uid).then_some(name))
             .expect("Master hasn't sent that userid")
     }
 }
@@ -97,13 +144,16 @@ mod tests {
         let alice = UserName {
             uid: ALICE_UID,
             name: "Alice".into(),
+            connection: Connection::Connected,
         };
         let bob = UserName {
             uid: BOB_UID,
             name: "Bob".into(),
+            connection: Connection::Lost,
         };
         let latest = UpdateFromMasterForNamer {
             names: UserNames(vec![alice, bob]),
+            spectators: vec!["Cedric".into()],
         };
         super::Namer { latest }
     }
@@ -3,11 +3,29 @@ use super::*;
 pub struct Session {
     client: game::client::session::Session,
     server: server::Server,
+    connection: Connection,
+    chat: chat::ChatHistory,
 }
 
 impl Session {
     pub fn new(client: game::client::session::Session, server: server::Server) -> Self {
-        Self { client, server }
+        let connection = Connection::Connected;
+        let chat = Default::default();
+        Self {
+            client,
+            server,
+            connection,
+            chat,
+        }
+    }
+
+    pub fn on_chat_from_master(&mut self, line: chat::ChatLine) {
+        self.chat.push(line);
+    }
+
+    pub fn on_master_lost(&mut self) {
+        println!("Lost the connection with the master");
+        self.connection = Connection::Lost;
     }
 
     pub fn on_updates_from_master_to_slave(&mut self, update: UpdateFromMaster) {
@@ -29,6 +47,9 @@ impl game::session::Session for Session {
         let user_stats = self.server.user_stats();
         let status = self.server.status();
         let coords = self.server.coords();
+        let players = self.server.players();
+        let spectators = self.server.spectators();
+        let teams = self.server.standings();
         let stats = self.client.stats();
         let field_provider = &self.client;
         let (local_player_listener, namer) = self.server.servitors();
@@ -36,6 +57,11 @@ impl game::session::Session for Session {
             user_stats,
             status,
             coords,
+            players,
+            spectators,
+            teams,
+            chat: Some(self.chat.clone()),
+            connection: self.connection,
             stats,
             namer,
             field_provider,
@@ -2,4 +2,6 @@ use super::*;
 
 pub trait MasterListener {
     fn on_updates_from_master_to_slave(&mut self, update: UpdateFromMaster);
+    fn on_chat_from_master(&mut self, line: chat::ChatLine);
+    fn on_master_lost(&mut self);
 }
@@ -10,14 +10,31 @@ pub struct UpdateFromMasterForClient {
 pub struct UserName {
     pub uid: SessionUserID,
     pub name: String,
+    pub connection: Connection,
 }
 
 #[derive(Clone, Debug)]
 pub struct UserNames(pub Vec<UserName>);
 
+impl UserNames {
+    pub fn players(&self) -> Vec<(SessionUserID, Connection)> {
+        let Self(names) = self;
+        names
+            .iter()
+            .map(
+                |UserName {
+                     uid, connection, ..
+                 }| (*uid, *connection),
+            )
+            .collect()
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct UpdateFromMasterForNamer {
     pub names: UserNames,
+    /// The names of those who watch without playing.
+    pub spectators: Vec<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -26,6 +43,8 @@ pub struct GameUpdateFromMasterForSlave {
     pub session: SessionID,
     pub status: Status,
     pub stats: UserStats,
+    /// None when there is only a single team.
+    pub standings: Option<Standings>,
 }
 
 #[derive(Clone, Debug)]
@@ -40,19 +59,72 @@ pub struct UpdateFromMaster {
     pub slave: UpdateFromMasterForSlave,
 }
 
+#[derive(Clone, Copy, Debug)]
+pub enum Action {
+    Uncover,
+    ToggleMark,
+    Chord,
+}
+
 #[derive(Debug)]
 pub struct ActionFromSlave {
     pub session: SessionID,
     pub coord: Coord,
-    pub left: bool,
+    pub action: Action,
+}
+
+/// Why a master doesn't let a slave join.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rejection {
+    VersionMismatch,
+    GameFull,
+    NameTaken,
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::VersionMismatch => write!(fmt, "version mismatch"),
+            Rejection::GameFull => write!(fmt, "game full"),
+            Rejection::NameTaken => write!(fmt, "name taken"),
+        }
+    }
+}
+
+/// Handed out by the master to a slave that joins, with which the slave can take its place back
+/// after losing its connection.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RejoinToken(pub u64);
+
+impl std::fmt::Display for RejoinToken {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let RejoinToken(token) = self;
+        write!(fmt, "{token}")
+    }
+}
+
+/// How a slave takes part in a session.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Role {
+    Player,
+    /// Watches the session, without taking up a place or being able to click.
+    Spectator,
 }
 
 #[derive(Debug)]
 pub struct RequestFromSlave {
     pub name: String,
+    pub role: Role,
+    pub token: Option<RejoinToken>,
 }
 
 pub enum MessageFromSlave {
     Join(RequestFromSlave),
     Action(ActionFromSlave),
+    Chat(String),
+}
+
+pub enum MessageFromMaster {
+    Update(Box<UpdateFromMaster>),
+    Chat(chat::ChatLine),
 }
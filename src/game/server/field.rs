@@ -1,13 +1,9 @@
 use super::*;
 use crate::game;
+use crate::game::layout::MineLayoutGenerator;
 
 use std::collections::{HashMap, HashSet};
 
-use rand::seq::SliceRandom;
-use rand::thread_rng;
-
-const RANDOM_FIELD: bool = true;
-
 #[allow(clippy::upper_case_acronyms)]
 type UID = SessionUserID;
 
@@ -37,25 +33,115 @@ pub struct Progress {
     pub remaining_mines: u32,
     pub exploded: u32,
     pub total_flags: u32,
+    pub uncovered_safe: u32,
 }
 
 pub struct Field {
     coords: Coordinations,
     cells: Vec<Cell>,
+    pending: Option<PendingLayout>,
+    /// Known once the mines are laid out.
+    three_bv: Option<u32>,
+    clicks: HashMap<UID, Clicks>,
+}
+
+/// The clicks of a player, which either changed something on the field or were wasted.
+#[derive(Clone, Copy, Default)]
+struct Clicks {
+    effective: u32,
+    wasted: u32,
+}
+
+/// Everything required to lay out the mines once the first cell gets uncovered.
+struct PendingLayout {
+    mines: Mines,
+    first_click: FirstClick,
+    generator: Box<dyn MineLayoutGenerator>,
 }
 
 impl Field {
-    pub fn new(coords: Coordinations, mines: Mines) -> Self {
-        let cells = Concept::random(coords, mines).to_cells();
-        Self::new_with_cells(coords, cells)
+    pub fn new(
+        coords: Coordinations,
+        mines: Mines,
+        first_click: FirstClick,
+        mut generator: Box<dyn MineLayoutGenerator>,
+    ) -> Self {
+        if let FirstClick::Unprotected = first_click {
+            let layout = generator.generate(&coords, mines, &[]);
+            let concept = Concept::new(coords.clone(), layout);
+            let mut field = Self::new_with_cells(coords, concept.to_cells(), None);
+            field.three_bv = Some(concept.three_bv());
+            return field;
+        }
+        let cell = Cell {
+            content: Content::Hint(Hint(0)),
+            status: Status::Covered,
+        };
+        let cells = vec![cell; coords.size()];
+        let pending = PendingLayout {
+            mines,
+            first_click,
+            generator,
+        };
+        Self::new_with_cells(coords, cells, Some(pending))
     }
 
-    fn new_with_cells(coords: Coordinations, cells: Vec<Cell>) -> Self {
-        Self { coords, cells }
+    fn new_with_cells(
+        coords: Coordinations,
+        cells: Vec<Cell>,
+        pending: Option<PendingLayout>,
+    ) -> Self {
+        Self {
+            coords,
+            cells,
+            pending,
+            three_bv: None,
+            clicks: HashMap::new(),
+        }
+    }
+
+    /// Lays out the mines, if that didn't happen yet, while keeping the first uncovered cell safe.
+    /// Cells that were already marked keep their status.
+    fn lay_out_mines_if_pending(&mut self, first: Index) {
+        let PendingLayout {
+            mines,
+            first_click,
+            mut generator,
+        } = match self.pending.take() {
+            Some(pending) => pending,
+            None => return,
+        };
+        let safe: Vec<Index> = match first_click {
+            FirstClick::Unprotected => Vec::new(),
+            FirstClick::Safe => vec![first],
+            FirstClick::Opening => std::iter::once(first)
+                .chain(self.coords.neighbors_at_index(first))
+                .collect(),
+        };
+        let layout = generator.generate(&self.coords, mines, &safe);
+        let concept = Concept::new(self.coords.clone(), layout);
+        for (cell, Cell { content, status: _ }) in self.cells.iter_mut().zip(concept.to_cells()) {
+            cell.content = content;
+        }
+        self.three_bv = Some(concept.three_bv());
     }
 
     pub fn coords(&self) -> Coordinations {
-        self.coords
+        self.coords.clone()
+    }
+
+    /// The minimum number of clicks which clear the minefield, once the mines are laid out.
+    pub fn three_bv(&self) -> Option<u32> {
+        self.three_bv
+    }
+
+    /// Counts a click of a player, which is wasted when it didn't change any cell.
+    fn click(&mut self, uid: UID, changed: bool) {
+        let clicks = self.clicks.entry(uid).or_default();
+        match changed {
+            true => clicks.effective += 1,
+            false => clicks.wasted += 1,
+        }
     }
 
     pub fn progress(&self) -> Progress {
@@ -69,16 +155,32 @@ impl Field {
                     && matches!(cell.status, Status::UncoveredBy(_))
             }),
             total_flags: self.count(|cell| matches!(cell.status, Status::MarkedBy(_))),
+            uncovered_safe: self.count(|cell| {
+                matches!(cell.content, Content::Hint(_))
+                    && matches!(cell.status, Status::UncoveredBy(_))
+            }),
         };
         progress.remaining_mines -= progress.exploded;
+        if let Some(PendingLayout {
+            mines: Mines(mines),
+            ..
+        }) = self.pending
+        {
+            progress.remaining_mines = mines;
+        }
         progress
     }
 
+    /// Counts the cells which are part of the field and pass the filter.
     fn count<F>(&self, filter: F) -> u32
     where
         F: Fn(&Cell) -> bool,
     {
-        self.cells.iter().filter(|cell| filter(cell)).count() as u32
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(index, cell)| self.coords.is_active(Index(*index)) && filter(cell))
+            .count() as u32
     }
 
     pub fn user_stats(&self) -> UserStats {
@@ -93,6 +195,7 @@ impl Field {
                 Status::EndGameCovered => None,
                 Status::EndGameMarkedBy(uid) => Some(uid),
             })
+            .chain(self.clicks.keys().copied())
             .map(|uid| (uid, UserStat::default()))
             .collect();
         for (&suid, stat) in &mut stats {
@@ -115,20 +218,82 @@ impl Field {
                         (Content::Mine, Status::UncoveredBy(uid)) if uid == suid => {
                             stat.exploded += 1
                         }
+                        (Content::Hint(hint), Status::UncoveredBy(uid)) if uid == suid => {
+                            stat.revealed += 1;
+                            if let Hint(1..) = hint {
+                                stat.hints += 1;
+                            }
+                        }
                         _ => {}
                     }
                     stat
                 },
             );
         }
+        for (uid, &Clicks { effective, wasted }) in &self.clicks {
+            if let Some(stat) = stats.get_mut(uid) {
+                stat.effective_clicks = effective;
+                stat.wasted_clicks = wasted;
+            }
+        }
+        for (uid, size) in self.openings() {
+            if let Some(stat) = stats.get_mut(&uid) {
+                stat.openings += 1;
+                stat.biggest_opening = stat.biggest_opening.max(size);
+            }
+        }
         UserStats(stats)
     }
 
+    /// Every area which got opened at once, by whom and how many cells it uncovered. An area
+    /// consists of connected empty cells, together with the hints around them.
+    fn openings(&self) -> Vec<(UID, u32)> {
+        let mut seen = HashSet::<Index>::new();
+        let mut openings = Vec::new();
+        for (index, cell) in self.cells.iter().enumerate() {
+            let uid = match (cell.content.is_empty_cell(), cell.status) {
+                (true, Status::UncoveredBy(uid)) if seen.insert(Index(index)) => uid,
+                _ => continue,
+            };
+            let mut border = HashSet::<Index>::new();
+            let mut empty = 1;
+            let mut todo = vec![Index(index)];
+            while let Some(i) = todo.pop() {
+                for neighbor_index in self.coords.neighbors_at_index(i) {
+                    let Index(neighbor_ci) = neighbor_index;
+                    let neighbor = &self.cells[neighbor_ci];
+                    if neighbor.status != Status::UncoveredBy(uid) {
+                        continue;
+                    }
+                    if !neighbor.content.is_empty_cell() {
+                        border.insert(neighbor_index);
+                    } else if seen.insert(neighbor_index) {
+                        empty += 1;
+                        todo.push(neighbor_index);
+                    }
+                }
+            }
+            openings.push((uid, empty + border.len() as u32));
+        }
+        openings
+    }
+
     pub fn uncover(&mut self, coord: &Coord, uid: UID) -> CellUpdates {
+        let updates = self.uncover_cell(coord, uid);
+        let CellUpdates(changed) = &updates;
+        self.click(uid, !changed.is_empty());
+        updates
+    }
+
+    fn uncover_cell(&mut self, coord: &Coord, uid: UID) -> CellUpdates {
         let ci = match self.coords.to_index(coord) {
             Some(index) => index,
             None => return CellUpdates::none(),
         };
+        if !self.coords.is_active(ci) {
+            return CellUpdates::none();
+        }
+        self.lay_out_mines_if_pending(ci);
         let Index(index) = ci;
         let cell = &mut self.cells[index];
         if let Status::UncoveredBy(_) = &cell.status {
@@ -159,6 +324,56 @@ impl Field {
         CellUpdates(updated_cells)
     }
 
+    /// Uncovers all covered neighbors of an uncovered hint, but only when that hint has as many
+    /// marked neighbors as it has mines around it. A wrongly placed mark makes a mine explode.
+    pub fn chord(&mut self, coord: &Coord, uid: UID) -> CellUpdates {
+        let updates = self.chord_cell(coord, uid);
+        let CellUpdates(changed) = &updates;
+        self.click(uid, !changed.is_empty());
+        updates
+    }
+
+    fn chord_cell(&mut self, coord: &Coord, uid: UID) -> CellUpdates {
+        let ci = match self.coords.to_index(coord) {
+            Some(index) => index,
+            None => return CellUpdates::none(),
+        };
+        let Index(index) = ci;
+        let hint = match self.cells[index] {
+            Cell {
+                content: Content::Hint(Hint(hint)),
+                status: Status::UncoveredBy(_),
+            } => hint as usize,
+            _ => return CellUpdates::none(),
+        };
+        let (marked, unmarked): (Vec<Index>, Vec<Index>) = self
+            .coords
+            .neighbors_at_index(ci)
+            .filter(|Index(neighbor_index)| {
+                matches!(
+                    self.cells[*neighbor_index].status,
+                    Status::Covered | Status::MarkedBy(_) | Status::QuestionMarkedBy(_)
+                )
+            })
+            .partition(|Index(neighbor_index)| {
+                matches!(self.cells[*neighbor_index].status, Status::MarkedBy(_))
+            });
+        if marked.len() != hint {
+            return CellUpdates::none();
+        }
+        let mut updated = HashSet::<Index>::new();
+        let mut updates = Vec::<CellUpdate>::new();
+        for i in unmarked {
+            let neighbor = self.coords.to_coord(i).expect("invalid index");
+            let CellUpdates(neighbor_updates) = self.uncover_cell(&neighbor, uid);
+            updates.extend(neighbor_updates.into_iter().filter(|update| {
+                let index = self.coords.to_index(&update.coord).expect("invalid coord");
+                updated.insert(index)
+            }));
+        }
+        CellUpdates(updates)
+    }
+
     fn gather_all_updated_cell_indices(&self, ci: Index) -> HashSet<Index> {
         let mut updated_cell_indices = HashSet::<Index>::new();
         let mut empty_cell_indices = HashSet::<Index>::new();
@@ -194,9 +409,15 @@ impl Field {
     }
 
     pub fn toggle_mark(&mut self, coord: &Coord, uid: UID) -> Option<CellUpdate> {
+        let update = self.toggle_mark_cell(coord, uid);
+        self.click(uid, update.is_some());
+        update
+    }
+
+    fn toggle_mark_cell(&mut self, coord: &Coord, uid: UID) -> Option<CellUpdate> {
         let Index(index) = match self.coords.to_index(coord) {
-            Some(index) => index,
-            None => return None,
+            Some(index) if self.coords.is_active(index) => index,
+            _ => return None,
         };
         let cell = &mut self.cells[index];
         match cell.status {
@@ -220,6 +441,31 @@ impl Field {
         })
     }
 
+    pub fn statuses(&self) -> Statuses {
+        Statuses(self.cells.iter().map(|cell| cell.status).collect())
+    }
+
+    /// Restores the status of every cell, and returns the updates of the cells that changed.
+    pub fn restore(&mut self, statuses: Statuses) -> CellUpdates {
+        let Statuses(statuses) = statuses;
+        let updates = self
+            .cells
+            .iter_mut()
+            .zip(statuses)
+            .enumerate()
+            .filter(|(_index, (cell, status))| cell.status != *status)
+            .map(|(index, (cell, status))| {
+                cell.status = status;
+                let coord = self.coords.to_coord(Index(index)).expect("invalid index");
+                CellUpdate {
+                    cell: (*cell).into(),
+                    coord,
+                }
+            })
+            .collect();
+        CellUpdates(updates)
+    }
+
     pub fn reveal_all(&mut self) {
         for cell in &mut self.cells {
             cell.status = match cell.status {
@@ -250,7 +496,10 @@ impl Field {
     }
 }
 
-#[derive(Clone, Copy)]
+/// The status of every cell, which can be restored later on.
+pub struct Statuses(Vec<Status>);
+
+#[derive(Clone, Copy, Eq, PartialEq)]
 enum Status {
     Covered,
     MarkedBy(UID),
@@ -288,44 +537,12 @@ struct Concept {
 }
 
 impl Concept {
-    fn random(coords: Coordinations, mines: Mines) -> Self {
-        let maximum_surrounding_mines = MAX_SURROUNDING_MINES as usize;
-        let Mines(number_of_mines) = mines;
-        let size = coords.size();
-        let mut mines = Vec::<bool>::new();
-        let mut indices = Vec::<Index>::new();
-        let mut rng = thread_rng();
-        'root: loop {
-            mines.clear();
-            mines.resize(size, false);
-
-            indices.clear();
-            indices.extend((0..size).map(Index));
-            if RANDOM_FIELD {
-                indices.shuffle(&mut rng);
-            }
-
-            for _ in 0..number_of_mines {
-                let Index(index) = indices.pop().unwrap();
-                mines[index] = true;
-            }
-            if !mines
-                .iter()
-                .enumerate()
-                .filter(|&(_index, mine)| *mine)
-                .map(|(index, _mine)| Index(index))
-                .all(|index| {
-                    let neighboring_mines = coords
-                        .neighbors_at_index(index)
-                        .filter(|Index(neighbor_index)| mines[*neighbor_index])
-                        .count();
-                    neighboring_mines <= maximum_surrounding_mines
-                })
-            {
-                continue 'root;
-            }
-            break 'root;
-        }
+    fn new(coords: Coordinations, mines: Vec<bool>) -> Self {
+        assert_eq!(
+            mines.len(),
+            coords.size(),
+            "Layout doesn't fit the minefield"
+        );
         Self { mines, coords }
     }
 
@@ -349,6 +566,45 @@ impl Concept {
             })
             .collect()
     }
+
+    /// The minimum number of clicks which clear the minefield, known as its 3BV: one for every
+    /// area of connected empty cells, and one for every other cell without a mine which doesn't
+    /// border such an area.
+    fn three_bv(&self) -> u32 {
+        let empty = |index: usize| {
+            !self.mines[index]
+                && self
+                    .coords
+                    .neighbors_at_index(Index(index))
+                    .all(|Index(neighbor)| !self.mines[neighbor])
+        };
+        let mut opened = vec![false; self.mines.len()];
+        let mut clicks = 0;
+        for index in 0..self.mines.len() {
+            if opened[index] || !self.coords.is_active(Index(index)) || !empty(index) {
+                continue;
+            }
+            clicks += 1;
+            opened[index] = true;
+            let mut todo = vec![index];
+            while let Some(i) = todo.pop() {
+                for Index(neighbor) in self.coords.neighbors_at_index(Index(i)) {
+                    if !opened[neighbor] {
+                        opened[neighbor] = true;
+                        if empty(neighbor) {
+                            todo.push(neighbor);
+                        }
+                    }
+                }
+            }
+        }
+        let isolated = (0..self.mines.len())
+            .filter(|&index| {
+                self.coords.is_active(Index(index)) && !self.mines[index] && !opened[index]
+            })
+            .count() as u32;
+        clicks + isolated
+    }
 }
 
 #[cfg(test)]
@@ -386,4 +642,146 @@ mod tests {
         let cell_content = cells.iter().map(|Cell { content, status: _ }| content);
         assert!(cell_content.zip(content.iter()).all(|(a, b)| a == b));
     }
+
+    const COORDS: Coordinations = Coordinations::from_width_and_height(16, 12);
+
+    fn seeded(seed: u64) -> Box<dyn MineLayoutGenerator> {
+        Box::new(game::layout::SeededLayout(Seed(seed)))
+    }
+
+    fn first_click_content(first_click: FirstClick, seed: u64) -> Content {
+        let coord = Coord { x: 5, y: 4 };
+        let mut field = Field::new(COORDS, Mines(40), first_click, seeded(seed));
+        let _ = field.uncover(&coord, SessionUserID(1));
+        let Index(index) = COORDS.to_index(&coord).unwrap();
+        field.cells[index].content
+    }
+
+    #[test]
+    fn safe_first_click_never_hits_a_mine() {
+        assert!((0..100).all(|seed| first_click_content(FirstClick::Safe, seed) != Content::Mine));
+    }
+
+    #[test]
+    fn opening_first_click_always_opens_an_area() {
+        assert!((0..100).all(|seed| first_click_content(FirstClick::Opening, seed).is_empty_cell()));
+    }
+
+    #[test]
+    fn safe_first_click_keeps_marks() {
+        let mut field = Field::new(COORDS, Mines(40), FirstClick::Safe, seeded(7));
+        let flag = Coord { x: 0, y: 0 };
+        assert!(field.toggle_mark(&flag, SessionUserID(1)).is_some());
+        let _ = field.uncover(&Coord { x: 5, y: 4 }, SessionUserID(1));
+        assert!(matches!(field.cells[0].status, Status::MarkedBy(_)));
+    }
+
+    fn create_field() -> Field {
+        let Concept { mines, coords } = create_concept();
+        let cells = Concept::new(coords.clone(), mines).to_cells();
+        Field::new_with_cells(coords, cells, None)
+    }
+
+    #[test]
+    fn chord_uncovers_neighbors_of_satisfied_hint() {
+        let mut field = create_field();
+        let uid = SessionUserID(1);
+        let _ = field.uncover(&Coord { x: 2, y: 2 }, uid);
+        assert!(field.toggle_mark(&Coord { x: 1, y: 1 }, uid).is_some());
+        let CellUpdates(updates) = field.chord(&Coord { x: 2, y: 2 }, uid);
+        assert_eq!(updates.len(), 2);
+        assert!(matches!(field.cells[5].status, Status::UncoveredBy(_)));
+        assert!(matches!(field.cells[7].status, Status::UncoveredBy(_)));
+    }
+
+    #[test]
+    fn chord_ignores_unsatisfied_hint() {
+        let mut field = create_field();
+        let uid = SessionUserID(1);
+        let _ = field.uncover(&Coord { x: 2, y: 2 }, uid);
+        let CellUpdates(updates) = field.chord(&Coord { x: 2, y: 2 }, uid);
+        assert!(updates.is_empty());
+        assert!(matches!(field.cells[5].status, Status::Covered));
+    }
+
+    #[test]
+    fn chord_with_wrong_mark_explodes() {
+        let mut field = create_field();
+        let uid = SessionUserID(1);
+        let _ = field.uncover(&Coord { x: 2, y: 2 }, uid);
+        assert!(field.toggle_mark(&Coord { x: 2, y: 1 }, uid).is_some());
+        let _ = field.chord(&Coord { x: 2, y: 2 }, uid);
+        assert_eq!(field.progress().exploded, 1);
+    }
+
+    #[test]
+    fn masked_cells_are_not_played() {
+        // the top right corner is not part of the field
+        let coords = Coordinations::from_width_and_height(3, 3).with_mask(vec![
+            true, true, false, // first row
+            true, true, true, // second row
+            true, true, true, // third row
+        ]);
+        let mut layout = vec![false; 9];
+        layout[0] = true;
+        let cells = Concept::new(coords.clone(), layout).to_cells();
+        let mut field = Field::new_with_cells(coords, cells, None);
+        let uid = SessionUserID(1);
+        assert!(field.toggle_mark(&Coord { x: 2, y: 0 }, uid).is_none());
+        let CellUpdates(updates) = field.uncover(&Coord { x: 2, y: 0 }, uid);
+        assert!(updates.is_empty());
+        let _ = field.uncover(&Coord { x: 2, y: 2 }, uid);
+        assert!(field.toggle_mark(&Coord { x: 0, y: 0 }, uid).is_some());
+        let progress = field.progress();
+        assert_eq!(progress.remaining_covered, 0);
+        assert_eq!(progress.remaining_mines, 0);
+    }
+
+    #[test]
+    fn user_stats_count_openings_and_hints() {
+        let coords = Coordinations::from_width_and_height(4, 3);
+        let mut layout = vec![false; 12];
+        layout[3] = true;
+        let cells = Concept::new(coords.clone(), layout).to_cells();
+        let mut field = Field::new_with_cells(coords, cells, None);
+        let uid = SessionUserID(1);
+        let _ = field.uncover(&Coord { x: 0, y: 2 }, uid);
+        let UserStats(stats) = field.user_stats();
+        let stat = stats[&uid];
+        assert_eq!(stat.revealed, 11);
+        assert_eq!(stat.hints, 3);
+        assert_eq!(stat.openings, 1);
+        assert_eq!(stat.biggest_opening, 11);
+    }
+
+    #[test]
+    fn three_bv_counts_openings_and_isolated_hints() {
+        assert_eq!(create_concept().three_bv(), 6);
+        let coords = Coordinations::from_width_and_height(5, 1);
+        let concept = Concept::new(coords, vec![false, false, true, false, false]);
+        assert_eq!(concept.three_bv(), 2);
+        let coords = Coordinations::from_width_and_height(5, 1);
+        let concept = Concept::new(coords, vec![false, true, false, true, false]);
+        assert_eq!(concept.three_bv(), 3);
+    }
+
+    #[test]
+    fn clicks_which_change_nothing_are_wasted() {
+        let mut field = create_field();
+        let uid = SessionUserID(1);
+        let _ = field.uncover(&Coord { x: 2, y: 2 }, uid);
+        let _ = field.uncover(&Coord { x: 2, y: 2 }, uid);
+        let _ = field.chord(&Coord { x: 2, y: 2 }, uid);
+        assert!(field.toggle_mark(&Coord { x: 1, y: 1 }, uid).is_some());
+        let _ = field.chord(&Coord { x: 2, y: 2 }, uid);
+        let UserStats(stats) = field.user_stats();
+        assert_eq!(stats[&uid].effective_clicks, 3);
+        assert_eq!(stats[&uid].wasted_clicks, 2);
+    }
+
+    #[test]
+    fn pending_field_is_not_yet_cleared() {
+        let field = Field::new(COORDS, Mines(40), FirstClick::Safe, seeded(7));
+        assert_eq!(field.progress().remaining_mines, 40);
+    }
 }
@@ -1,4 +1,7 @@
 mod field;
+mod score;
 pub mod session;
+pub mod solver;
+pub mod timer;
 
 use super::*;
@@ -0,0 +1,65 @@
+//! How many points the players earn with what they did on the minefield.
+
+use super::*;
+
+/// For every cell without a mine which a player uncovered, so a click that opens an area earns
+/// as much as all of its cells.
+pub const REVEALED_POINTS: i32 = 10;
+pub const MARKED_CORRECT_POINTS: i32 = 25;
+pub const MARKED_INCORRECT_PENALTY: i32 = 25;
+pub const EXPLODED_PENALTY: i32 = 100;
+/// The time per cell without a mine in which a minefield has to be cleared to earn a speed
+/// bonus. The faster, the closer the bonus gets to the points for the uncovered cells.
+pub const PAR_PER_CELL: Duration = Duration::from_millis(500);
+
+/// The share of the points for uncovered cells which is earned on top, for clearing `safe` cells
+/// in `elapsed` time.
+pub fn speed_bonus(elapsed: Duration, safe: u32) -> f64 {
+    let par = PAR_PER_CELL * safe;
+    if par.is_zero() {
+        return 0.;
+    }
+    (1. - elapsed.as_secs_f64() / par.as_secs_f64()).max(0.)
+}
+
+/// The points a player earned.
+pub fn score(stat: &UserStat, speed_bonus: f64) -> i32 {
+    let UserStat {
+        marked_correct,
+        marked_incorrect,
+        exploded,
+        revealed,
+        ..
+    } = *stat;
+    let revealed = revealed as i32 * REVEALED_POINTS;
+    let bonus = (f64::from(revealed) * speed_bonus).round() as i32;
+    revealed + bonus + marked_correct as i32 * MARKED_CORRECT_POINTS
+        - marked_incorrect as i32 * MARKED_INCORRECT_PENALTY
+        - exploded as i32 * EXPLODED_PENALTY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_rewards_cells_and_flags_and_punishes_mistakes() {
+        let stat = UserStat {
+            marked_correct: 2,
+            marked_incorrect: 1,
+            exploded: 1,
+            revealed: 12,
+            ..Default::default()
+        };
+        assert_eq!(score(&stat, 0.), 120 + 50 - 25 - 100);
+        assert_eq!(score(&stat, 0.5), 120 + 60 + 50 - 25 - 100);
+    }
+
+    #[test]
+    fn speed_bonus_shrinks_until_par() {
+        assert_eq!(speed_bonus(Duration::ZERO, 10), 1.);
+        assert_eq!(speed_bonus(Duration::from_millis(2500), 10), 0.5);
+        assert_eq!(speed_bonus(Duration::from_secs(60), 10), 0.);
+        assert_eq!(speed_bonus(Duration::from_secs(1), 0), 0.);
+    }
+}
@@ -1,56 +1,178 @@
 use super::*;
-use crate::game::server::field;
+use crate::game::layout;
+use crate::game::server::{field, score, timer};
+use crate::game::session::SessionConfig;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
 
 pub struct Session {
     field: field::Field,
     mines: Mines,
     lives: Lives,
+    lives_policy: LivesPolicy,
+    /// The players who joined, besides those who already made a move. When every player has
+    /// lives of their own, the session is only lost once all of them ran out.
+    roster: HashSet<SessionUserID>,
     progress: field::Progress,
+    /// The statuses of the cells before every move that can still be undone.
+    /// `None` when moves can't be undone.
+    history: Option<Vec<field::Statuses>>,
+    timer: timer::Timer,
 }
 
 impl Session {
-    pub fn new(coords: Coordinations, mines: Mines, lives: Lives) -> Self {
+    pub fn new(config: &SessionConfig) -> Self {
+        let (config, generator) = Self::generator(config);
+        Self::with_generator(&config, generator)
+    }
+
+    /// The generator which lays out the mines of a session played with `config`, together with
+    /// the config it has to be played with.
+    pub fn generator(
+        config: &SessionConfig,
+    ) -> (SessionConfig, Box<dyn layout::MineLayoutGenerator>) {
+        let seed = || {
+            let seed = config.seed.unwrap_or_else(Seed::random);
+            let Seed(s) = seed;
+            println!("Minefield seed: {s}");
+            seed
+        };
+        match &config.generator {
+            Generator::Random => (config.clone(), Box::new(layout::SeededLayout(seed()))),
+            Generator::NoGuess(budget) => {
+                let config = SessionConfig {
+                    first_click: FirstClick::Opening,
+                    ..config.clone()
+                };
+                let generator = layout::NoGuessLayout {
+                    seed: seed(),
+                    budget: *budget,
+                };
+                (config, Box::new(generator))
+            }
+            Generator::Custom(generator) => (config.clone(), Box::new(generator.clone())),
+        }
+    }
+
+    /// Creates a session of which the mines are laid out by `generator`, regardless of the
+    /// generator and seed in `config`.
+    pub fn with_generator(
+        config: &SessionConfig,
+        generator: Box<dyn layout::MineLayoutGenerator>,
+    ) -> Self {
+        let SessionConfig {
+            coords,
+            mines,
+            lives,
+            lives_policy,
+            first_click,
+            undo,
+            ..
+        } = config;
         let progress = field::Progress {
             remaining_covered: 1,
             ..Default::default()
         }; // can't let it by default be done
-        let field = field::Field::new(coords, mines);
+        let field = field::Field::new(coords.clone(), *mines, *first_click, generator);
         Self {
             field,
-            mines,
-            lives,
+            mines: *mines,
+            lives: *lives,
+            lives_policy: *lives_policy,
+            roster: HashSet::new(),
             progress,
+            history: undo.then(Vec::new),
+            timer: timer::Timer::default(),
         }
     }
 
     pub fn uncover(&mut self, coord: &Coord, uid: SessionUserID) -> CellUpdates {
-        if self.status().done() {
+        if !self.status().done() {
+            self.timer.start(Instant::now());
+        }
+        self.play(uid, |field| field.uncover(coord, uid))
+    }
+
+    pub fn chord(&mut self, coord: &Coord, uid: SessionUserID) -> CellUpdates {
+        self.play(uid, |field| field.chord(coord, uid))
+    }
+
+    pub fn toggle_mark(&mut self, coord: &Coord, uid: SessionUserID) -> CellUpdates {
+        self.play(uid, |field| {
+            field
+                .toggle_mark(coord, uid)
+                .map(CellUpdates::one)
+                .unwrap_or_default()
+        })
+    }
+
+    /// Plays a move on the field, remembering what it looked like before when moves can be
+    /// undone.
+    fn play<F>(&mut self, uid: SessionUserID, f: F) -> CellUpdates
+    where
+        F: FnOnce(&mut field::Field) -> CellUpdates,
+    {
+        if self.status().done() || self.out_of_lives(uid) {
             return CellUpdates::default();
         }
-        let mut updates = self.field.uncover(coord, uid);
+        self.timer.act(uid, Instant::now());
+        let before = self.history.is_some().then(|| self.field.statuses());
+        let mut updates = f(&mut self.field);
+        if let (Some(history), Some(before)) = (&mut self.history, before) {
+            let CellUpdates(changed) = &updates;
+            if !changed.is_empty() {
+                history.push(before);
+            }
+        }
         if let Some(last_updates) = self.update_progress() {
             updates = last_updates
         }
         updates
     }
 
-    pub fn toggle_mark(&mut self, coord: &Coord, uid: SessionUserID) -> CellUpdates {
-        if self.status().done() {
-            return CellUpdates::default();
+    /// Reverses the last move, including any lives it cost, and returns the updates of all cells
+    /// that got reverted. Does nothing when moves can't be undone, or when more than one player
+    /// takes part, as the last move might not be theirs.
+    pub fn undo(&mut self) -> CellUpdates {
+        let UserStats(stats) = self.field.user_stats();
+        let players: HashSet<_> = self.roster.iter().chain(stats.keys()).collect();
+        if players.len() > 1 {
+            return CellUpdates::none();
         }
-        let update = self.field.toggle_mark(coord, uid);
-        let last_updates = self.update_progress();
-        if let Some(last_updates) = last_updates {
-            last_updates
-        } else {
-            update.map(CellUpdates::one).unwrap_or_default()
+        let statuses = match self.history.as_mut().and_then(Vec::pop) {
+            Some(statuses) => statuses,
+            None => return CellUpdates::none(),
+        };
+        let updates = self.field.restore(statuses);
+        self.progress = self.field.progress();
+        if !self.status().done() {
+            self.timer.resume(Instant::now());
         }
+        updates
+    }
+
+    /// Registers a player who joined, before they made any move.
+    pub fn join(&mut self, uid: SessionUserID) {
+        self.roster.insert(uid);
+    }
+
+    /// Forgets about a player who left, unless they already made a move.
+    pub fn leave(&mut self, uid: SessionUserID) {
+        self.roster.remove(&uid);
     }
 
     pub fn status(&self) -> Status {
         let p = &self.progress;
-        let stats = self.stats();
-        if stats.lives_left == 0 {
+        let lost = match self.lives_policy {
+            LivesPolicy::Shared => self.stats().lives_left == Some(0),
+            LivesPolicy::PerPlayer => {
+                let UserStats(stats) = self.field.user_stats();
+                let mut players = self.roster.iter().chain(stats.keys()).peekable();
+                players.peek().is_some() && players.all(|&uid| self.out_of_lives_with(&stats, uid))
+            }
+            LivesPolicy::Unlimited => false,
+        };
+        if lost {
             Status::Ended { success: false }
         } else if p.remaining_mines == 0 && p.remaining_covered == 0 {
             Status::Ended { success: true }
@@ -60,29 +182,81 @@ impl Session {
     }
 
     pub fn user_stats(&self) -> UserStats {
-        self.field.user_stats()
+        let UserStats(mut stats) = self.field.user_stats();
+        if self.lives_policy == LivesPolicy::PerPlayer {
+            let Lives(lives) = self.lives;
+            for stat in stats.values_mut() {
+                stat.lives_left = Some(lives.saturating_sub(stat.exploded));
+            }
+        }
+        // only a cleared minefield earns a speed bonus
+        let speed_bonus = match self.status() {
+            Status::Ended { success: true } => {
+                let (_, safe) = self.cleared();
+                score::speed_bonus(self.stats().clock.elapsed(), safe)
+            }
+            _ => 0.,
+        };
+        for stat in stats.values_mut() {
+            stat.score = score::score(stat, speed_bonus);
+        }
+        UserStats(stats)
+    }
+
+    /// Whether or not the player can't play anymore, because their own lives are gone.
+    pub fn out_of_lives(&self, uid: SessionUserID) -> bool {
+        if self.lives_policy != LivesPolicy::PerPlayer {
+            return false;
+        }
+        let UserStats(stats) = self.field.user_stats();
+        self.out_of_lives_with(&stats, uid)
+    }
+
+    fn out_of_lives_with(
+        &self,
+        stats: &HashMap<SessionUserID, UserStat>,
+        uid: SessionUserID,
+    ) -> bool {
+        let Lives(lives) = self.lives;
+        stats.get(&uid).is_some_and(|stat| stat.exploded >= lives)
+    }
+
+    /// When every player who made a move was active.
+    pub fn activities(&self) -> HashMap<SessionUserID, Activity> {
+        self.timer.activities()
     }
 
     pub fn stats(&self) -> Stats {
         let Lives(lives) = self.lives;
-        let lives_left = lives - self.progress.exploded;
+        let lives_left = (self.lives_policy == LivesPolicy::Shared)
+            .then(|| lives.saturating_sub(self.progress.exploded));
         let Mines(mines) = self.mines;
         let p = &self.progress;
         let mines_left = mines as i32 - p.total_flags as i32 - p.exploded as i32;
         Stats {
             mines_left,
             lives_left,
+            clock: self.timer.clock(Instant::now()),
+            three_bv: self.field.three_bv(),
         }
     }
 
     fn update_progress(&mut self) -> Option<CellUpdates> {
         self.progress = self.field.progress();
         self.status().done().then(|| {
+            self.timer.stop(Instant::now());
             self.field.reveal_all();
             self.field.all()
         })
     }
 
+    /// The number of cells without a mine which are uncovered, and how many there are.
+    pub fn cleared(&self) -> (u32, u32) {
+        let Mines(mines) = self.mines;
+        let safe = self.field.coords().active_size() as u32 - mines;
+        (self.progress.uncovered_safe, safe)
+    }
+
     pub fn coords(&self) -> Coordinations {
         self.field.coords()
     }
@@ -91,3 +265,171 @@ impl Session {
         self.field.all()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(layout: &str, undo: bool) -> Session {
+        session_with_policy(layout, undo, LivesPolicy::Shared)
+    }
+
+    fn session_with_policy(layout: &str, undo: bool, lives_policy: LivesPolicy) -> Session {
+        let layout: layout::FileLayout = layout.parse().unwrap();
+        let config = SessionConfig {
+            coords: layout.coords(),
+            mines: layout.mines(),
+            lives: Lives(3),
+            lives_policy,
+            seed: None,
+            first_click: FirstClick::Unprotected,
+            generator: Generator::Random,
+            undo,
+        };
+        Session::with_generator(&config, Box::new(layout))
+    }
+
+    const UID: SessionUserID = SessionUserID(1);
+
+    #[test]
+    fn undo_covers_opened_area() {
+        let mut session = session("*...\n....\n....\n", true);
+        let CellUpdates(opened) = session.uncover(&Coord { x: 3, y: 2 }, UID);
+        let CellUpdates(reverted) = session.undo();
+        assert_eq!(opened.len(), 11);
+        assert_eq!(reverted.len(), 11);
+        assert!(reverted
+            .iter()
+            .all(|CellUpdate { cell, coord: _ }| matches!(cell, Cell::Covered)));
+    }
+
+    #[test]
+    fn undo_restores_lost_life() {
+        let mut session = session("*...\n....\n", true);
+        let _ = session.toggle_mark(&Coord { x: 3, y: 1 }, UID);
+        let _ = session.uncover(&Coord { x: 0, y: 0 }, UID);
+        assert_eq!(session.stats().lives_left, Some(2));
+        let _ = session.undo();
+        assert_eq!(session.stats().lives_left, Some(3));
+        let _ = session.undo();
+        assert_eq!(session.stats().mines_left, 1);
+        let CellUpdates(nothing) = session.undo();
+        assert!(nothing.is_empty());
+    }
+
+    #[test]
+    fn undo_can_be_disabled() {
+        let mut session = session("*...\n....\n", false);
+        let _ = session.uncover(&Coord { x: 0, y: 0 }, UID);
+        let CellUpdates(nothing) = session.undo();
+        assert!(nothing.is_empty());
+        assert_eq!(session.stats().lives_left, Some(2));
+    }
+
+    #[test]
+    fn undo_is_only_for_a_single_player() {
+        let mut session = session("*...\n....\n", true);
+        session.join(UID);
+        session.join(SessionUserID(2));
+        let _ = session.uncover(&Coord { x: 3, y: 1 }, UID);
+        let CellUpdates(nothing) = session.undo();
+        assert!(nothing.is_empty());
+        session.leave(SessionUserID(2));
+        let CellUpdates(reverted) = session.undo();
+        assert!(!reverted.is_empty());
+        let _ = session.uncover(&Coord { x: 3, y: 1 }, UID);
+        let _ = session.toggle_mark(&Coord { x: 0, y: 0 }, SessionUserID(2));
+        session.leave(SessionUserID(2));
+        let CellUpdates(nothing) = session.undo();
+        assert!(nothing.is_empty());
+    }
+
+    #[test]
+    fn clock_runs_from_first_uncover_until_the_end() {
+        let mut session = session("*...\n....\n", false);
+        assert_eq!(session.stats().clock, Clock::Idle);
+        let _ = session.uncover(&Coord { x: 3, y: 1 }, UID);
+        assert!(matches!(session.stats().clock, Clock::Running(_)));
+        let _ = session.uncover(&Coord { x: 0, y: 1 }, UID);
+        let _ = session.toggle_mark(&Coord { x: 0, y: 0 }, UID);
+        assert!(session.status().done());
+        assert!(matches!(session.stats().clock, Clock::Stopped(_)));
+    }
+
+    #[test]
+    fn cleared_counts_only_safe_cells() {
+        let mut session = session("*...\n....\n", false);
+        assert_eq!(session.cleared(), (0, 7));
+        let _ = session.uncover(&Coord { x: 0, y: 1 }, UID);
+        let _ = session.uncover(&Coord { x: 0, y: 0 }, UID);
+        assert_eq!(session.cleared(), (1, 7));
+        let _ = session.uncover(&Coord { x: 3, y: 1 }, UID);
+        assert_eq!(session.cleared(), (7, 7));
+    }
+
+    #[test]
+    fn players_with_lives_of_their_own_only_lose_those() {
+        let mut session = session_with_policy("***.\n....\n....\n", false, LivesPolicy::PerPlayer);
+        let other = SessionUserID(2);
+        for x in 0..3 {
+            let _ = session.uncover(&Coord { x, y: 0 }, UID);
+        }
+        assert_eq!(session.stats().lives_left, None);
+        assert!(session.out_of_lives(UID));
+        assert!(session.status().done());
+        let CellUpdates(nothing) = session.uncover(&Coord { x: 3, y: 2 }, UID);
+        assert!(nothing.is_empty());
+        let mut session = session_with_policy("***.\n....\n....\n", false, LivesPolicy::PerPlayer);
+        let _ = session.toggle_mark(&Coord { x: 3, y: 2 }, other);
+        for x in 0..3 {
+            let _ = session.uncover(&Coord { x, y: 0 }, UID);
+        }
+        assert!(!session.status().done());
+        let UserStats(stats) = session.user_stats();
+        assert_eq!(stats[&UID].lives_left, Some(0));
+        assert_eq!(stats[&other].lives_left, Some(3));
+    }
+
+    #[test]
+    fn players_who_joined_keep_the_session_going() {
+        let mut session = session_with_policy("***.\n....\n....\n", false, LivesPolicy::PerPlayer);
+        let other = SessionUserID(2);
+        session.join(UID);
+        session.join(other);
+        for x in 0..3 {
+            let _ = session.uncover(&Coord { x, y: 0 }, UID);
+        }
+        assert!(!session.status().done());
+        session.leave(other);
+        assert!(session.status().done());
+    }
+
+    #[test]
+    fn unlimited_lives_never_run_out() {
+        let mut session = session_with_policy("***.\n....\n", false, LivesPolicy::Unlimited);
+        for x in 0..3 {
+            let _ = session.uncover(&Coord { x, y: 0 }, UID);
+        }
+        assert!(!session.status().done());
+        assert_eq!(session.stats().lives_left, None);
+        let UserStats(stats) = session.user_stats();
+        assert_eq!(stats[&UID].exploded, 3);
+        assert_eq!(stats[&UID].lives_left, None);
+    }
+
+    #[test]
+    fn players_score_for_what_they_uncover() {
+        let mut session = session("*...\n....\n", false);
+        let other = SessionUserID(2);
+        let _ = session.uncover(&Coord { x: 0, y: 1 }, UID);
+        let _ = session.uncover(&Coord { x: 0, y: 0 }, other);
+        let UserStats(stats) = session.user_stats();
+        assert_eq!(stats[&UID].score, score::REVEALED_POINTS);
+        assert_eq!(stats[&other].score, -score::EXPLODED_PENALTY);
+        let _ = session.uncover(&Coord { x: 3, y: 1 }, UID);
+        assert!(session.status().done());
+        // cleared right away, which nearly doubles the points
+        let UserStats(stats) = session.user_stats();
+        assert_eq!(stats[&UID].score, 2 * 7 * score::REVEALED_POINTS);
+    }
+}
@@ -0,0 +1,260 @@
+//! A deterministic solver which plays a minefield the way a careful player would: it only
+//! uncovers or flags a cell when the revealed hints prove it is safe or a mine.
+
+use super::*;
+
+use std::collections::VecDeque;
+
+/// Determines whether or not a minefield can be cleared without guessing, starting by
+/// uncovering `first`.
+pub fn solvable(coords: &Coordinations, mines: &[bool], first: Index) -> bool {
+    let mut solver = Solver::new(coords, mines);
+    if !solver.reveal(first) {
+        return false;
+    }
+    solver.solve()
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Knowledge {
+    Unknown,
+    Safe,
+    Mine,
+}
+
+struct Solver<'a> {
+    coords: &'a Coordinations,
+    mines: &'a [bool],
+    hints: Vec<u8>,
+    knowledge: Vec<Knowledge>,
+    /// Revealed cells of which the neighborhood changed since they were last looked at.
+    dirty: VecDeque<Index>,
+    safe_left: usize,
+    mines_left: usize,
+}
+
+impl<'a> Solver<'a> {
+    fn new(coords: &'a Coordinations, mines: &'a [bool]) -> Self {
+        let hints = (0..mines.len())
+            .map(|index| {
+                coords
+                    .neighbors_at_index(Index(index))
+                    .filter(|Index(neighbor_index)| mines[*neighbor_index])
+                    .count() as u8
+            })
+            .collect();
+        let mines_left = mines.iter().filter(|mine| **mine).count();
+        // Cells outside of the field are considered to be known safe cells without any neighbors.
+        let knowledge = (0..mines.len())
+            .map(|index| {
+                if coords.is_active(Index(index)) {
+                    Knowledge::Unknown
+                } else {
+                    Knowledge::Safe
+                }
+            })
+            .collect();
+        Self {
+            coords,
+            mines,
+            hints,
+            knowledge,
+            dirty: VecDeque::new(),
+            safe_left: coords.active_size() - mines_left,
+            mines_left,
+        }
+    }
+
+    fn solve(&mut self) -> bool {
+        loop {
+            while let Some(index) = self.dirty.pop_front() {
+                self.apply_single(index);
+            }
+            if self.safe_left == 0 {
+                return true;
+            }
+            if !self.apply_pairs() && !self.apply_global() {
+                return false;
+            }
+        }
+    }
+
+    /// Uncovers a cell, and all cells around it when it has no neighboring mines.
+    /// Returns `false` when a mine got uncovered.
+    fn reveal(&mut self, index: Index) -> bool {
+        let mut todo = vec![index];
+        while let Some(index) = todo.pop() {
+            let Index(i) = index;
+            if self.knowledge[i] != Knowledge::Unknown {
+                continue;
+            }
+            if self.mines[i] {
+                return false;
+            }
+            self.knowledge[i] = Knowledge::Safe;
+            self.safe_left -= 1;
+            self.dirty.push_back(index);
+            self.mark_neighbors_dirty(index);
+            if self.hints[i] == 0 {
+                todo.extend(self.coords.neighbors_at_index(index));
+            }
+        }
+        true
+    }
+
+    fn flag(&mut self, index: Index) {
+        let Index(i) = index;
+        if self.knowledge[i] != Knowledge::Unknown {
+            return;
+        }
+        self.knowledge[i] = Knowledge::Mine;
+        self.mines_left -= 1;
+        self.mark_neighbors_dirty(index);
+    }
+
+    fn mark_neighbors_dirty(&mut self, index: Index) {
+        let revealed = self
+            .coords
+            .neighbors_at_index(index)
+            .filter(|Index(neighbor_index)| self.knowledge[*neighbor_index] == Knowledge::Safe);
+        self.dirty.extend(revealed);
+    }
+
+    /// Returns the unknown neighbors of a revealed cell, and how many mines are among them.
+    fn unknown_neighbors(&self, index: Index) -> (Vec<Index>, usize) {
+        let Index(i) = index;
+        let mut flagged = 0;
+        let mut unknown = Vec::new();
+        for neighbor in self.coords.neighbors_at_index(index) {
+            let Index(neighbor_index) = neighbor;
+            match self.knowledge[neighbor_index] {
+                Knowledge::Unknown => unknown.push(neighbor),
+                Knowledge::Mine => flagged += 1,
+                Knowledge::Safe => {}
+            }
+        }
+        (unknown, self.hints[i] as usize - flagged)
+    }
+
+    /// Looks at a single hint: either all of its unknown neighbors are mines, or none are.
+    fn apply_single(&mut self, index: Index) {
+        let (unknown, mines) = self.unknown_neighbors(index);
+        if !unknown.is_empty() {
+            self.settle(&unknown, mines);
+        }
+    }
+
+    /// Reveals or flags all given cells if the number of mines among them allows it.
+    fn settle(&mut self, cells: &[Index], mines: usize) -> bool {
+        if mines == 0 {
+            for &cell in cells {
+                let safe = self.reveal(cell);
+                debug_assert!(safe, "the solver uncovered a mine");
+            }
+            true
+        } else if mines == cells.len() {
+            for &cell in cells {
+                self.flag(cell);
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Compares two hints for which the unknown neighbors of the first are a subset of the
+    /// unknown neighbors of the second one. The remaining mines of the second hint must then be
+    /// located in the difference between both.
+    fn apply_pairs(&mut self) -> bool {
+        let frontier: Vec<Index> = (0..self.knowledge.len())
+            .map(Index)
+            .filter(|&index| {
+                let Index(i) = index;
+                self.knowledge[i] == Knowledge::Safe
+                    && self
+                        .coords
+                        .neighbors_at_index(index)
+                        .any(|Index(n)| self.knowledge[n] == Knowledge::Unknown)
+            })
+            .collect();
+        for &a in &frontier {
+            let (unknown_a, mines_a) = self.unknown_neighbors(a);
+            let candidates: Vec<Index> = unknown_a
+                .iter()
+                .flat_map(|&u| self.coords.neighbors_at_index(u))
+                .filter(|&b| {
+                    let Index(i) = b;
+                    b != a && self.knowledge[i] == Knowledge::Safe
+                })
+                .collect();
+            for b in candidates {
+                let (unknown_b, mines_b) = self.unknown_neighbors(b);
+                if mines_b < mines_a || !unknown_a.iter().all(|u| unknown_b.contains(u)) {
+                    continue;
+                }
+                let difference: Vec<Index> = unknown_b
+                    .into_iter()
+                    .filter(|u| !unknown_a.contains(u))
+                    .collect();
+                if !difference.is_empty() && self.settle(&difference, mines_b - mines_a) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Uses the total number of mines left.
+    fn apply_global(&mut self) -> bool {
+        let unknown: Vec<Index> = (0..self.knowledge.len())
+            .filter(|&i| self.knowledge[i] == Knowledge::Unknown)
+            .map(Index)
+            .collect();
+        !unknown.is_empty() && self.settle(&unknown, self.mines_left)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_mine_in_corner_is_solvable() {
+        let coords = Coordinations::from_width_and_height(3, 3);
+        let mut mines = vec![false; 9];
+        mines[0] = true;
+        assert!(solvable(&coords, &mines, Index(8)));
+    }
+
+    #[test]
+    fn fifty_fifty_is_not_solvable() {
+        let coords = Coordinations::from_width_and_height(4, 2);
+        // M . . .
+        // . . . .
+        let mut mines = vec![false; 8];
+        mines[0] = true;
+        assert!(!solvable(&coords, &mines, Index(3)));
+    }
+
+    #[test]
+    fn starting_on_a_mine_is_not_solvable() {
+        let coords = Coordinations::from_width_and_height(3, 3);
+        let mut mines = vec![false; 9];
+        mines[4] = true;
+        assert!(!solvable(&coords, &mines, Index(4)));
+    }
+
+    #[test]
+    fn pair_of_hints_resolves_mines() {
+        let coords = Coordinations::from_width_and_height(3, 4);
+        // . . .
+        // . . .
+        // 1 1 1
+        // . M .
+        // None of the hints on the third row is conclusive by itself, but comparing the first two
+        // shows that the bottom right corner is safe.
+        let mut mines = vec![false; 12];
+        mines[10] = true;
+        assert!(solvable(&coords, &mines, Index(0)));
+    }
+}
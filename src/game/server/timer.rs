@@ -0,0 +1,121 @@
+use super::*;
+use std::time::{Duration, Instant};
+
+/// Keeps track of when a session started and ended, and of when every player was active.
+#[derive(Default)]
+pub struct Timer {
+    started: Option<Instant>,
+    ended: Option<Instant>,
+    /// How long the session sat finished before it got resumed, which the clock doesn't count.
+    paused: Duration,
+    activities: HashMap<SessionUserID, Activity>,
+}
+
+impl Timer {
+    /// Starts the clock, unless it is already running.
+    pub fn start(&mut self, now: Instant) {
+        self.started.get_or_insert(now);
+    }
+
+    pub fn stop(&mut self, now: Instant) {
+        if self.started.is_some() {
+            self.ended.get_or_insert(now);
+        }
+    }
+
+    /// Keeps the clock running after it was stopped, as if it never stopped.
+    pub fn resume(&mut self, now: Instant) {
+        if let Some(ended) = self.ended.take() {
+            self.paused += now.saturating_duration_since(ended);
+        }
+    }
+
+    /// Remembers when the player acted, as the clock shows it.
+    pub fn act(&mut self, uid: SessionUserID, now: Instant) {
+        let time = self.clock(now).elapsed();
+        self.activities
+            .entry(uid)
+            .and_modify(|activity| activity.last = time)
+            .or_insert(Activity {
+                first: time,
+                last: time,
+            });
+    }
+
+    /// When every player was active. Actions before the start of the clock count as at the
+    /// start.
+    pub fn activities(&self) -> HashMap<SessionUserID, Activity> {
+        self.activities.clone()
+    }
+
+    pub fn clock(&self, now: Instant) -> Clock {
+        let since = |started: Instant, time: Instant| {
+            time.saturating_duration_since(started)
+                .saturating_sub(self.paused)
+        };
+        match (self.started, self.ended) {
+            (None, _) => Clock::Idle,
+            (Some(started), None) => Clock::Running(since(started, now)),
+            (Some(started), Some(ended)) => Clock::Stopped(since(started, ended)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn clock_runs_from_start_until_stop() {
+        let t0 = Instant::now();
+        let second = Duration::from_secs(1);
+        let mut timer = Timer::default();
+        assert_eq!(timer.clock(t0), Clock::Idle);
+        timer.stop(t0);
+        assert_eq!(timer.clock(t0 + second), Clock::Idle);
+        timer.start(t0);
+        timer.start(t0 + second);
+        assert_eq!(timer.clock(t0 + 2 * second), Clock::Running(2 * second));
+        timer.stop(t0 + 3 * second);
+        assert_eq!(timer.clock(t0 + 5 * second), Clock::Stopped(3 * second));
+    }
+
+    #[test]
+    fn clock_skips_the_time_it_was_stopped() {
+        let t0 = Instant::now();
+        let second = Duration::from_secs(1);
+        let uid = SessionUserID::new(1);
+        let mut timer = Timer::default();
+        timer.start(t0);
+        timer.stop(t0 + 2 * second);
+        timer.resume(t0 + 10 * second);
+        assert_eq!(timer.clock(t0 + 11 * second), Clock::Running(3 * second));
+        timer.act(uid, t0 + 11 * second);
+        timer.stop(t0 + 12 * second);
+        assert_eq!(timer.clock(t0 + 20 * second), Clock::Stopped(4 * second));
+        let activity = Activity {
+            first: 3 * second,
+            last: 3 * second,
+        };
+        assert_eq!(timer.activities().get(&uid), Some(&activity));
+    }
+
+    #[test]
+    fn activity_keeps_first_and_last_action() {
+        let t0 = Instant::now();
+        let second = Duration::from_secs(1);
+        let uid = SessionUserID::new(1);
+        let mut timer = Timer::default();
+        assert_eq!(timer.activities().get(&uid), None);
+        timer.act(uid, t0);
+        timer.start(t0 + second);
+        timer.act(uid, t0 + 2 * second);
+        timer.act(uid, t0 + 3 * second);
+        let activity = Activity {
+            first: Duration::ZERO,
+            last: 2 * second,
+        };
+        assert_eq!(timer.activities().get(&uid), Some(&activity));
+    }
+}
@@ -1,10 +1,24 @@
 use super::*;
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct SessionConfig {
     pub coords: Coordinations,
     pub mines: Mines,
     pub lives: Lives,
+    pub lives_policy: LivesPolicy,
+    /// When `None`, every new session picks a random seed.
+    pub seed: Option<Seed>,
+    pub first_click: FirstClick,
+    pub generator: Generator,
+    /// Whether or not moves can be undone.
+    pub undo: bool,
+}
+
+impl SessionConfig {
+    /// The lives which the players share, if they do.
+    pub fn shared_lives(&self) -> Option<Lives> {
+        (self.lives_policy == LivesPolicy::Shared).then_some(self.lives)
+    }
 }
 
 pub struct SessionSnapshot<'a> {
@@ -12,6 +26,16 @@ pub struct SessionSnapshot<'a> {
     pub user_stats: UserStats,
     pub status: Status,
     pub coords: Coordinations,
+    /// Everyone who takes part in the session.
+    pub players: Vec<(SessionUserID, Connection)>,
+    /// The names of those who only watch.
+    pub spectators: Vec<String>,
+    /// How the teams are doing, when teams compete.
+    pub teams: Option<Standings>,
+    /// What the players said, when there is anyone to chat with.
+    pub chat: Option<chat::ChatHistory>,
+    /// Whether or not this side can still reach the session.
+    pub connection: Connection,
     pub namer: &'a dyn Namer,
     pub field_provider: &'a dyn FieldProvider,
     pub local_player_listener: &'a mut dyn LocalPlayerListener,
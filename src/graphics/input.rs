@@ -5,6 +5,16 @@ pub struct Input {
     pub cursor_pos: CursorPosition,
     pub draw_size: DrawSize,
     pub mouse_down: MouseDown,
+    pub chat: ChatInput,
+}
+
+/// What the player is typing, and how far the chat log is scrolled back.
+#[derive(Default)]
+pub struct ChatInput {
+    /// Only while typing a message.
+    pub draft: Option<String>,
+    /// The number of rows scrolled back from the newest.
+    pub scroll: usize,
 }
 
 #[derive(Clone, Default, Copy)]
@@ -47,19 +57,30 @@ impl From<[u32; 2]> for DrawSize {
 pub struct MouseDown {
     pub left: bool,
     pub right: bool,
+    pub middle: bool,
+}
+
+/// What a mouse button press does to the cell below the cursor.
+pub enum Press {
+    Single,
+    /// Pressing both the left and the right button, or the middle button.
+    Chord,
 }
 
 impl MouseDown {
     fn left_or_right<F>(state: pw::ButtonState, when_down: F, button: &mut bool, other_button: bool)
     where
-        F: FnOnce(),
+        F: FnOnce(Press),
     {
         match state {
             pw::ButtonState::Press => {
-                let mouse_free = !*button && !other_button;
-                if mouse_free {
+                if !*button {
                     *button = true;
-                    when_down()
+                    when_down(if other_button {
+                        Press::Chord
+                    } else {
+                        Press::Single
+                    })
                 }
             }
             pw::ButtonState::Release => {
@@ -70,15 +91,23 @@ impl MouseDown {
 
     pub fn left<F>(&mut self, state: pw::ButtonState, when_down: F)
     where
-        F: FnOnce(),
+        F: FnOnce(Press),
     {
         Self::left_or_right(state, when_down, &mut self.left, self.right)
     }
 
     pub fn right<F>(&mut self, state: pw::ButtonState, when_down: F)
     where
-        F: FnOnce(),
+        F: FnOnce(Press),
     {
         Self::left_or_right(state, when_down, &mut self.right, self.left)
     }
+
+    pub fn middle<F>(&mut self, state: pw::ButtonState, when_down: F)
+    where
+        F: FnOnce(Press),
+    {
+        let other_button = true;
+        Self::left_or_right(state, when_down, &mut self.middle, other_button)
+    }
 }
@@ -1,4 +1,5 @@
 mod input;
+mod shape;
 mod status;
 mod textures;
 
@@ -6,8 +7,9 @@ use super::*;
 
 use crate::coordinations::*;
 pub use status::{
-    Active, Cell, Controller, Grid, LivesLeft, MinesLeft, NonActive, Statistics, Status,
-    StatusGenerator, UserStat,
+    Active, Board, Cell, ChatLine, ClickStat, Controller, Grid, LivesLeft, MinesLeft, NonActive,
+    Outcome, PlayerState, Statistics, Status, StatusGenerator, TeamProgress, Time, UncoverStat,
+    UserStat,
 };
 
 use piston_window as pw;
@@ -15,14 +17,19 @@ use pw::Transformed;
 
 use itertools::iproduct;
 
-use std::collections::HashMap;
-
 pub static WINDOW_DEFAULT_TITLE: &str = "Sioux minesweeper, in Rust!";
 const WINDOW_DEFAULT_WIDTH: u32 = 640;
 const WINDOW_DEFAULT_HEIGHT: u32 = 480;
 
 const DUMMY_PLAYER_ID: PlayerID = PlayerID(0);
 
+/// The number of rows of the chat log which are shown at once.
+const CHAT_ROWS: usize = 10;
+/// The number of characters in a row of the chat log, longer lines are wrapped.
+const CHAT_ROW_LENGTH: usize = 24;
+/// The number of characters in the progress bar of a team.
+const PROGRESS_BAR_LENGTH: u32 = 10;
+
 static BACKGROUND_COLOR: Color = WHITE;
 static WHITE: Color = [1.0, 1.0, 1.0, 1.0];
 static GREEN: Color = [0.5, 1.0, 0.5, 1.0];
@@ -153,7 +160,7 @@ fn construct_canvas_and_transform(
     fit: Option<Fit>,
 ) -> (Canvas, Transform) {
     let canvas_target_ratio = CanvasRatio {
-        w_over_h: coords.w_over_h(),
+        w_over_h: shape::Shape::new(coords).w_over_h(),
     };
     let canvas = fit.map_or(canvas.clone(), |fit| {
         fit.rebuild_canvas(canvas, canvas_size, canvas_target_ratio)
@@ -175,16 +182,10 @@ fn identify_cell(
     let (_, transform) =
         construct_canvas_and_transform(context_transform, coords, canvas, &canvas_size, fit);
 
-    let inv = pw::math::invert(transform);
-    let [x, y] = pw::math::transform_pos(inv, (*cursor_pos).into());
-    ((0. ..=1.).contains(&x) && (0. ..=1.).contains(&y)).then(|| {
-        let x = x * coords.columns() as f64;
-        let y = y * coords.rows() as f64;
-        Coord {
-            x: x as u32,
-            y: y as u32,
-        }
-    })
+    let shape = shape::Shape::new(coords);
+    let [w, h] = shape.size();
+    let inv = pw::math::invert(transform.scale(1. / w, 1. / h));
+    shape.identify(pw::math::transform_pos(inv, (*cursor_pos).into()))
 }
 
 type GLTexture = pw::Texture<gfx_device_gl::Resources>;
@@ -204,92 +205,120 @@ fn draw_grid<'a, G, P>(
     P: Fn(&Coord) -> (Option<&'a GLTexture>, Option<PlayerID>),
 {
     let canvas_size: CanvasSize = (*draw_size).into();
-    let (canvas, transform) =
+    let (_, transform) =
         construct_canvas_and_transform(&context.transform, coords, canvas, &canvas_size, fit);
-    let h = 1. / coords.rows() as f64;
-    let w = 1. / coords.columns() as f64;
+    let shape = shape::Shape::new(coords);
+    let [w, h] = shape.size();
+    let transform = transform.scale(1. / w, 1. / h);
     // draw fill
-    for row in 0..coords.rows() {
-        let y = row as f64 * h;
-        for column in 0..coords.columns() {
-            let x = column as f64 * w;
-            let (_texture, player) = players_and_textures(&Coord { x: column, y: row });
-            if let Some(player) = player {
-                let rectangle = pw::Rectangle::new(player.color());
-                let dims = [x, y, w, h];
-                rectangle.draw(dims, &context.draw_state, transform, graphics);
-            }
+    for coord in shape.cells() {
+        let (_texture, player) = players_and_textures(&coord);
+        if let Some(player) = player {
+            let polygon = pw::Polygon::new(player.color());
+            polygon.draw(
+                &shape.outline(&coord),
+                &context.draw_state,
+                transform,
+                graphics,
+            );
         }
     }
     // draw textures
-    {
-        let w = canvas.w / coords.columns() as f64;
-        let h = canvas.h / coords.rows() as f64;
-        let rect_image: pw::Image = pw::Image::new().rect(pw::rectangle::square(0.0, 0.0, 1.0));
-        for row in 0..coords.rows() {
-            let y = canvas.y + row as f64 * h;
-            for column in 0..coords.columns() {
-                let (texture, _player) = players_and_textures(&Coord { x: column, y: row });
-                if let Some(texture) = texture {
-                    let x = canvas.x + column as f64 * w;
-                    let canvas = Canvas { x, y, w, h };
-                    let transform = rect_transform(&context.transform, &canvas, &canvas_size);
-                    rect_image.draw(texture, &context.draw_state, transform, graphics);
-                }
-            }
+    let rect_image: pw::Image = pw::Image::new().rect(pw::rectangle::square(0.0, 0.0, 1.0));
+    for coord in shape.cells() {
+        let (texture, _player) = players_and_textures(&coord);
+        if let Some(texture) = texture {
+            let [x, y, w, h] = shape.inner_square(&coord);
+            let transform = transform.trans(x, y).scale(w, h);
+            rect_image.draw(texture, &context.draw_state, transform, graphics);
         }
     }
     // draw lines
-    for row in 0..coords.rows() + 1 {
-        let y = row as f64 * h;
-        let line = [0., y, 1., y];
-        pw::line(BLACK, 0.05 * h, line, transform, graphics);
-    }
-    for column in 0..coords.columns() + 1 {
-        let x = column as f64 * w;
-        let line = [x, 0., x, 1.];
-        pw::line(BLACK, 0.05 * w, line, transform, graphics);
+    for coord in shape.cells() {
+        let outline = shape.outline(&coord);
+        let corners = outline.iter().zip(outline.iter().cycle().skip(1));
+        for (&[x1, y1], &[x2, y2]) in corners {
+            pw::line(BLACK, 0.05, [x1, y1, x2, y2], transform, graphics);
+        }
     }
 }
 
 #[allow(clippy::too_many_arguments)]
-fn draw_active_text<'a, G, P>(
+fn draw_active_text<G>(
     context: &pw::Context,
     graphics: &mut G,
-    coords: &Coordinations,
     canvas: &Canvas,
     canvas_size: &CanvasSize,
     fit: Option<Fit>,
     glyphs: &mut pw::Glyphs,
     v: &rusttype::VMetrics,
-    players: P,
+    players: &[status::PlayerState],
+    spectators: &[String],
+    rivals: &[status::TeamProgress],
     lives_left: status::LivesLeft,
     mines_left: status::MinesLeft,
+    time: status::Time,
 ) where
     G: pw::Graphics<Texture = GLTexture>,
-    P: Fn(&Coord) -> Option<Player<'a>>,
 {
-    let (mines_text, lives_text) = {
+    let (mines_text, lives_text, time_text) = {
         let status::MinesLeft(mines_left) = mines_left;
         let status::LivesLeft(lives_left) = lives_left;
         let mines_text = format!("Mines: {}", mines_left);
-        let lives_text = format!("Lives: {}", lives_left);
-        (mines_text, lives_text)
+        let lives_text = lives_left.map(|lives_left| format!("Lives: {}", lives_left));
+        let time_text = format!("Time: {}", time);
+        (mines_text, lives_text, time_text)
     };
     let texts = {
-        let players: HashMap<PlayerID, &str> = iproduct!(0..coords.rows(), 0..coords.columns())
-            .map(|(row, column)| players(&Coord { x: column, y: row }))
-            .filter_map(|player: Option<Player<'a>>| player.map(|player| (player.id, player.name)))
+        let mut texts: Vec<(PlayerID, String)> = players
+            .iter()
+            .map(
+                |status::PlayerState {
+                     id,
+                     name,
+                     connected,
+                     lives_left,
+                     score,
+                 }| match (connected, lives_left) {
+                    (false, _) => (*id, format!("{name}: {score} (disconnected)")),
+                    (true, Some(0)) => (*id, format!("{name}: {score} (out of lives)")),
+                    (true, Some(lives_left)) => {
+                        (*id, format!("{name}: {score} ({lives_left} lives)"))
+                    }
+                    (true, None) => (*id, format!("{name}: {score}")),
+                },
+            )
             .collect();
-        let mut texts: Vec<(PlayerID, &str)> = players.into_iter().collect();
-        texts.push((DUMMY_PLAYER_ID, &mines_text));
-        texts.push((DUMMY_PLAYER_ID, &lives_text));
-        texts.sort_by_key(|&(id, _name)| id);
+        texts.push((DUMMY_PLAYER_ID, mines_text));
+        texts.extend(lives_text.map(|lives_text| (DUMMY_PLAYER_ID, lives_text)));
+        texts.push((DUMMY_PLAYER_ID, time_text));
+        texts.sort_by_key(|&(id, _)| id);
+        if !spectators.is_empty() {
+            texts.push((DUMMY_PLAYER_ID, "Spectators:".into()));
+            texts.extend(
+                spectators
+                    .iter()
+                    .map(|name| (DUMMY_PLAYER_ID, name.clone())),
+            );
+        }
+        texts.extend(rivals.iter().map(|rival| {
+            let status::TeamProgress {
+                name,
+                uncovered,
+                safe,
+                lives_left,
+                ..
+            } = rival;
+            let bar = progress_bar(*uncovered, *safe);
+            let lives =
+                lives_left.map_or(String::new(), |lives_left| format!(" {lives_left} lives"));
+            (DUMMY_PLAYER_ID, format!("{name} [{bar}]{lives}"))
+        }));
         texts
     };
 
     draw_text(
-        texts.iter().map(|(id, name)| (*id, (*name).to_string())),
+        texts.iter().cloned(),
         texts.len(),
         context,
         graphics,
@@ -301,6 +330,74 @@ fn draw_active_text<'a, G, P>(
     )
 }
 
+/// Shows how much of the safe cells got uncovered, like `#####-----` for half of them.
+fn progress_bar(uncovered: u32, safe: u32) -> String {
+    let filled = (uncovered * PROGRESS_BAR_LENGTH)
+        .checked_div(safe)
+        .unwrap_or(PROGRESS_BAR_LENGTH)
+        .min(PROGRESS_BAR_LENGTH);
+    let filled = filled as usize;
+    let empty = PROGRESS_BAR_LENGTH as usize - filled;
+    format!("{}{}", "#".repeat(filled), "-".repeat(empty))
+}
+
+/// Draws the newest rows of the chat log, or older ones when scrolled back, above the message
+/// which is being typed.
+#[allow(clippy::too_many_arguments)]
+fn draw_chat<G>(
+    context: &pw::Context,
+    graphics: &mut G,
+    canvas: &Canvas,
+    canvas_size: &CanvasSize,
+    glyphs: &mut pw::Glyphs,
+    v: &rusttype::VMetrics,
+    chat: &[status::ChatLine],
+    input: &mut input::ChatInput,
+) where
+    G: pw::Graphics<Texture = GLTexture>,
+{
+    let rows: Vec<(PlayerID, String)> = chat
+        .iter()
+        .flat_map(|status::ChatLine { id, name, text }| {
+            let line: Vec<char> = format!("{name}: {text}").chars().collect();
+            line.chunks(CHAT_ROW_LENGTH)
+                .map(|row| (*id, row.iter().collect()))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    input.scroll = input.scroll.min(rows.len().saturating_sub(CHAT_ROWS));
+    let end = rows.len() - input.scroll;
+    let start = end.saturating_sub(CHAT_ROWS);
+    let typing = match &input.draft {
+        Some(draft) => {
+            let draft: Vec<char> = draft.chars().collect();
+            let tail = draft.len().saturating_sub(CHAT_ROW_LENGTH - 2);
+            let tail: String = draft[tail..].iter().collect();
+            format!("> {tail}_")
+        }
+        None => "Enter to chat".into(),
+    };
+    let fit = Some(Fit(FitHorizontal::Left, FitVertical::Center));
+    draw_text(
+        rows[start..end]
+            .iter()
+            .cloned()
+            .chain(std::iter::repeat_n(
+                (DUMMY_PLAYER_ID, " ".into()),
+                CHAT_ROWS - (end - start),
+            ))
+            .chain(std::iter::once((DUMMY_PLAYER_ID, typing))),
+        CHAT_ROWS + 1,
+        context,
+        graphics,
+        canvas,
+        canvas_size,
+        fit,
+        glyphs,
+        v,
+    )
+}
+
 #[allow(clippy::too_many_arguments)]
 fn draw_text<G, T>(
     texts: T,
@@ -366,7 +463,7 @@ fn draw_statistics_text<G>(
     glyphs: &mut pw::Glyphs,
     v: &rusttype::VMetrics,
     stats: &status::Statistics,
-    success: bool,
+    outcome: status::Outcome,
 ) where
     G: pw::Graphics<Texture = GLTexture>,
 {
@@ -375,18 +472,117 @@ fn draw_statistics_text<G>(
         let status::UserStat { id, name, number } = stat;
         (*id, format!("{} ({})", name, number))
     };
-    let summary = if success { "YOU WON!" } else { "You Failed" };
+    let summary = match outcome {
+        status::Outcome::Won => "YOU WON!",
+        status::Outcome::Failed => "You Failed",
+        status::Outcome::Disconnected => "Connection to master lost",
+    };
+    let time = format!("Time: {}", stats.time);
+    let teams: Vec<(PlayerID, String)> = stats
+        .teams
+        .iter()
+        .map(|team| {
+            let status::TeamProgress {
+                name,
+                players,
+                uncovered,
+                safe,
+                lives_left,
+                winner,
+            } = team;
+            let winner = match winner {
+                true => " WINNER",
+                false => "",
+            };
+            let players = players.join(", ");
+            let lives =
+                lives_left.map_or(String::new(), |lives_left| format!(", {lives_left} lives"));
+            let text = format!("{name}{winner}: {uncovered}/{safe} uncovered{lives} ({players})");
+            single(&text)
+        })
+        .collect();
+    let uncovered: Vec<(PlayerID, String)> = stats
+        .uncovered
+        .iter()
+        .map(|stat| {
+            let status::UncoverStat {
+                id,
+                name,
+                revealed,
+                hints,
+                openings,
+                biggest_opening,
+            } = stat;
+            let text = format!(
+                "{name} ({revealed} cells, {hints} hints, {openings} openings, biggest {biggest_opening})"
+            );
+            (*id, text)
+        })
+        .collect();
+    let clicks: Vec<(PlayerID, String)> = stats
+        .clicks
+        .iter()
+        .map(|stat| {
+            let status::ClickStat {
+                id,
+                name,
+                effective,
+                wasted,
+            } = stat;
+            (
+                *id,
+                format!("{name} ({effective} effective, {wasted} wasted)"),
+            )
+        })
+        .collect();
+    let board = stats.board.as_ref().map(|board| {
+        let status::Board {
+            three_bv,
+            three_bv_per_second,
+            efficiency,
+        } = board;
+        let per_second = three_bv_per_second.map_or(String::new(), |per_second| {
+            format!(", {per_second:.2} 3BV/s")
+        });
+        let efficiency = efficiency.map_or(String::new(), |efficiency| {
+            format!(", {:.0}% efficiency", efficiency * 100.)
+        });
+        format!("3BV: {three_bv}{per_second}{efficiency}")
+    });
+    let leader = match stats.scores.as_slice() {
+        [first, second, ..] if first.number == second.number => Some("It's a tie".to_string()),
+        [first, _, ..] => Some(format!("{} has the most points", first.name)),
+        _ => None,
+    };
     let texts = std::iter::once(single(summary))
+        .chain(std::iter::once(single(&time)))
+        .chain(board.iter().map(|board| single(board)))
+        .chain(teams.iter().cloned())
+        .chain(leader.iter().map(|leader| single(leader)))
+        .chain(std::iter::once(single("Leaderboard:")))
+        .chain(stats.scores.iter().map(convert))
         .chain(std::iter::once(single("Correct Flags:")))
         .chain(stats.marked_correct.iter().map(convert))
         .chain(std::iter::once(single("Exploded Mines:")))
         .chain(stats.exploded.iter().map(convert))
         .chain(std::iter::once(single("InCorrect Flags:")))
-        .chain(stats.marked_incorrect.iter().map(convert));
+        .chain(stats.marked_incorrect.iter().map(convert))
+        .chain(std::iter::once(single("Uncovered:")))
+        .chain(uncovered.iter().cloned())
+        .chain(std::iter::once(single("Clicks:")))
+        .chain(clicks.iter().cloned());
 
     draw_text(
         texts,
-        4 + stats.marked_correct.len() + stats.exploded.len() + stats.marked_incorrect.len(),
+        8 + teams.len()
+            + usize::from(board.is_some())
+            + usize::from(leader.is_some())
+            + stats.scores.len()
+            + stats.marked_correct.len()
+            + stats.exploded.len()
+            + stats.marked_incorrect.len()
+            + uncovered.len()
+            + clicks.len(),
         context,
         graphics,
         canvas,
@@ -473,14 +669,14 @@ where
                 &mut glyphs,
                 &v_metrics,
             ),
-            status::Status::NonActive { nonactive, success } => nonactive_event(
+            status::Status::NonActive { nonactive, outcome } => nonactive_event(
                 nonactive,
                 event,
                 &mut input,
                 &mut window,
                 &mut glyphs,
                 &v_metrics,
-                success,
+                outcome,
             ),
         })
     }
@@ -489,10 +685,26 @@ where
 enum CheckInput {
     MouseLeft,
     MouseRight,
+    Chord,
+    Undo,
+    Chat(String),
+}
+
+impl CheckInput {
+    fn pressed(single: Self, press: input::Press) -> Self {
+        match press {
+            input::Press::Single => single,
+            input::Press::Chord => Self::Chord,
+        }
+    }
 }
 
-fn check_input(i: pw::Input, input: &mut input::Input) -> Option<CheckInput> {
+/// Keys only type into the chat while `can_chat`, after pressing return.
+fn check_input(i: pw::Input, input: &mut input::Input, can_chat: bool) -> Option<CheckInput> {
     let mut ci = None;
+    if !can_chat {
+        input.chat.draft = None;
+    }
     match i {
         pw::Input::Resize(pw::ResizeArgs {
             draw_size: d,
@@ -507,16 +719,61 @@ fn check_input(i: pw::Input, input: &mut input::Input) -> Option<CheckInput> {
             state,
             button: pw::Button::Mouse(pw::MouseButton::Right),
             scancode: _,
-        }) => input.mouse_down.right(state, || {
-            ci.replace(CheckInput::MouseRight);
+        }) => input.mouse_down.right(state, |press| {
+            ci.replace(CheckInput::pressed(CheckInput::MouseRight, press));
         }),
         pw::Input::Button(pw::ButtonArgs {
             state,
             button: pw::Button::Mouse(pw::MouseButton::Left),
             scancode: _,
-        }) => input.mouse_down.left(state, || {
-            ci.replace(CheckInput::MouseLeft);
+        }) => input.mouse_down.left(state, |press| {
+            ci.replace(CheckInput::pressed(CheckInput::MouseLeft, press));
+        }),
+        pw::Input::Button(pw::ButtonArgs {
+            state,
+            button: pw::Button::Mouse(pw::MouseButton::Middle),
+            scancode: _,
+        }) => input.mouse_down.middle(state, |press| {
+            ci.replace(CheckInput::pressed(CheckInput::Chord, press));
         }),
+        pw::Input::Move(pw::Motion::MouseScroll([_, y])) => {
+            let scroll = &mut input.chat.scroll;
+            *scroll = match y > 0. {
+                true => scroll.saturating_add(1),
+                false => scroll.saturating_sub(1),
+            };
+        }
+        pw::Input::Button(pw::ButtonArgs {
+            state: pw::ButtonState::Press,
+            button: pw::Button::Keyboard(pw::Key::Return),
+            scancode: _,
+        }) if can_chat => match input.chat.draft.take() {
+            Some(text) => {
+                ci.replace(CheckInput::Chat(text));
+            }
+            None => input.chat.draft = Some(String::new()),
+        },
+        pw::Input::Button(pw::ButtonArgs {
+            state: pw::ButtonState::Press,
+            button: pw::Button::Keyboard(pw::Key::Backspace),
+            scancode: _,
+        }) => {
+            if let Some(draft) = &mut input.chat.draft {
+                draft.pop();
+            }
+        }
+        pw::Input::Text(text) => {
+            if let Some(draft) = &mut input.chat.draft {
+                draft.extend(text.chars().filter(|c| !c.is_control()));
+            }
+        }
+        pw::Input::Button(pw::ButtonArgs {
+            state: pw::ButtonState::Press,
+            button: pw::Button::Keyboard(pw::Key::U),
+            scancode: _,
+        }) if input.chat.draft.is_none() => {
+            ci.replace(CheckInput::Undo);
+        }
         _ => {}
     }
     ci
@@ -540,11 +797,21 @@ fn active_event<G>(
         w: 0.76,
         h: 0.96,
     };
+    // the chat goes below the names, when there is one
     let names_canvas = Canvas {
         x: 0.82,
         y: 0.02,
         w: 0.16,
-        h: 0.96,
+        h: match active.chat {
+            Some(_) => 0.46,
+            None => 0.96,
+        },
+    };
+    let chat_canvas = Canvas {
+        x: 0.82,
+        y: 0.50,
+        w: 0.16,
+        h: 0.48,
     };
     let fit = Some(Fit(FitHorizontal::Center, FitVertical::Center));
 
@@ -561,7 +828,7 @@ fn active_event<G>(
         )
     };
     match event {
-        pw::Event::Input(i, _) => match check_input(i, input) {
+        pw::Event::Input(i, _) => match check_input(i, input, active.chat.is_some()) {
             Some(CheckInput::MouseRight) => {
                 if let Some(coord) = cell_clicked() {
                     active.grid.right_click_cell(&coord);
@@ -572,6 +839,13 @@ fn active_event<G>(
                     active.grid.left_click_cell(&coord);
                 }
             }
+            Some(CheckInput::Chord) => {
+                if let Some(coord) = cell_clicked() {
+                    active.grid.chord_cell(&coord);
+                }
+            }
+            Some(CheckInput::Undo) => active.grid.undo(),
+            Some(CheckInput::Chat(text)) => active.grid.chat(&text),
             None => {}
         },
         pw::Event::Loop(pw::Loop::Render(render_args)) => {
@@ -590,16 +864,30 @@ fn active_event<G>(
                 draw_active_text(
                     &c,
                     g,
-                    &active.coords,
                     &names_canvas,
                     &render_args.draw_size.into(),
                     fit,
                     glyphs,
                     v_metrics,
-                    |coord| active.grid.get_cell(coord).player().copied(),
+                    &active.players,
+                    &active.spectators,
+                    &active.rivals,
                     active.lives_left,
                     active.mines_left,
+                    active.time,
                 );
+                if let Some(chat) = &active.chat {
+                    draw_chat(
+                        &c,
+                        g,
+                        &chat_canvas,
+                        &render_args.draw_size.into(),
+                        glyphs,
+                        v_metrics,
+                        chat,
+                        &mut input.chat,
+                    );
+                }
                 glyphs.factory.encoder.flush(d);
             });
         }
@@ -614,7 +902,7 @@ fn nonactive_event<C>(
     window: &mut pw::PistonWindow,
     glyphs: &mut pw::Glyphs,
     v_metrics: &rusttype::VMetrics,
-    success: bool,
+    outcome: status::Outcome,
 ) where
     C: status::Controller,
 {
@@ -645,7 +933,7 @@ fn nonactive_event<C>(
                     glyphs,
                     v_metrics,
                     statistics,
-                    success,
+                    outcome,
                 );
                 glyphs.factory.encoder.flush(d);
             });
@@ -659,7 +947,7 @@ fn nonactive_event<C>(
                 controller.request_new_game();
             }
             _ => {
-                check_input(i, input);
+                check_input(i, input, false);
             }
         },
         _ => {}
@@ -0,0 +1,157 @@
+//! The geometry of the cells, as determined by the topology of the field.
+//! All positions are measured in cell widths, starting at the top left of the grid.
+
+use super::*;
+
+pub struct Shape {
+    coords: Coordinations,
+}
+
+impl Shape {
+    pub fn new(coords: &Coordinations) -> Self {
+        let coords = coords.clone();
+        Self { coords }
+    }
+
+    /// Returns the width and the height of the whole grid.
+    pub fn size(&self) -> [f64; 2] {
+        let columns = self.coords.columns() as f64;
+        let rows = self.coords.rows() as f64;
+        match self.coords.topology() {
+            Topology::Rectangular | Topology::Toroidal => [columns, rows],
+            Topology::Hexagonal => [columns + 0.5, hexagon_radius() * (1.5 * rows + 0.5)],
+        }
+    }
+
+    /// Returns all cells which are part of the field, and should thus be drawn.
+    pub fn cells(&self) -> impl Iterator<Item = Coord> + '_ {
+        iproduct!(0..self.coords.rows(), 0..self.coords.columns())
+            .map(|(row, column)| Coord { x: column, y: row })
+            .filter(|coord| self.contains(coord))
+    }
+
+    fn contains(&self, coord: &Coord) -> bool {
+        self.coords
+            .to_index(coord)
+            .is_some_and(|index| self.coords.is_active(index))
+    }
+
+    pub fn w_over_h(&self) -> f64 {
+        let [w, h] = self.size();
+        w / h
+    }
+
+    fn center(&self, coord: &Coord) -> [f64; 2] {
+        let Coord { x, y } = *coord;
+        let (x, y, odd_row) = (x as f64, y as f64, y % 2 == 1);
+        match self.coords.topology() {
+            Topology::Rectangular | Topology::Toroidal => [x + 0.5, y + 0.5],
+            Topology::Hexagonal => {
+                let shift = if odd_row { 0.5 } else { 0. };
+                let r = hexagon_radius();
+                [x + 0.5 + shift, r + 1.5 * r * y]
+            }
+        }
+    }
+
+    /// Returns the corners of a cell, clockwise.
+    pub fn outline(&self, coord: &Coord) -> Vec<[f64; 2]> {
+        let [x, y] = self.center(coord);
+        match self.coords.topology() {
+            Topology::Rectangular | Topology::Toroidal => vec![
+                [x - 0.5, y - 0.5],
+                [x + 0.5, y - 0.5],
+                [x + 0.5, y + 0.5],
+                [x - 0.5, y + 0.5],
+            ],
+            Topology::Hexagonal => {
+                let r = hexagon_radius();
+                vec![
+                    [x, y - r],
+                    [x + 0.5, y - 0.5 * r],
+                    [x + 0.5, y + 0.5 * r],
+                    [x, y + r],
+                    [x - 0.5, y + 0.5 * r],
+                    [x - 0.5, y - 0.5 * r],
+                ]
+            }
+        }
+    }
+
+    /// Returns the largest square that fits within a cell, as `[x, y, w, h]`.
+    pub fn inner_square(&self, coord: &Coord) -> [f64; 4] {
+        let [x, y] = self.center(coord);
+        let side = match self.coords.topology() {
+            Topology::Rectangular | Topology::Toroidal => 1.,
+            Topology::Hexagonal => {
+                let r = hexagon_radius();
+                2. * r / (1. + r)
+            }
+        };
+        [x - 0.5 * side, y - 0.5 * side, side, side]
+    }
+
+    /// Determines which cell contains a position.
+    pub fn identify(&self, pos: [f64; 2]) -> Option<Coord> {
+        let [x, y] = pos;
+        match self.coords.topology() {
+            Topology::Rectangular | Topology::Toroidal => {
+                let coord = Coord {
+                    x: x as u32,
+                    y: y as u32,
+                };
+                (x >= 0. && y >= 0.)
+                    .then_some(coord)
+                    .filter(|coord| self.contains(coord))
+            }
+            Topology::Hexagonal => {
+                // Within a hexagonal grid, a position belongs to the cell with the nearest center.
+                let r = hexagon_radius();
+                let row = ((y - r) / (1.5 * r)).round() as i64;
+                let column = x.floor() as i64;
+                let distance = |coord: &Coord| {
+                    let [cx, cy] = self.center(coord);
+                    (cx - x).powi(2) + (cy - y).powi(2)
+                };
+                iproduct!(row - 1..=row + 1, column - 1..=column + 1)
+                    .filter(|&(row, column)| row >= 0 && column >= 0)
+                    .map(|(row, column)| Coord {
+                        x: column as u32,
+                        y: row as u32,
+                    })
+                    .filter(|coord| self.contains(coord))
+                    .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+                    .filter(|coord| distance(coord) <= r * r)
+            }
+        }
+    }
+}
+
+/// The distance between the center and the corners of a hexagon which is one cell width wide.
+fn hexagon_radius() -> f64 {
+    1. / 3f64.sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identify_rectangular_cell() {
+        let shape = Shape::new(&Coordinations::from_width_and_height(5, 4));
+        assert_eq!(shape.identify([2.5, 3.9]), Some(Coord { x: 2, y: 3 }));
+        assert_eq!(shape.identify([5.1, 1.]), None);
+    }
+
+    #[test]
+    fn identify_hexagonal_cell() {
+        let coords = Coordinations::from_width_and_height(5, 4).with_topology(Topology::Hexagonal);
+        let shape = Shape::new(&coords);
+        for coord in (0..coords.size()).filter_map(|index| coords.to_coord(Index(index))) {
+            assert_eq!(shape.identify(shape.center(&coord)), Some(coord));
+        }
+        // the left half of the first cell of an odd row is empty
+        let [_x, y] = shape.center(&Coord { x: 0, y: 1 });
+        assert_eq!(shape.identify([0.2, y]), None);
+    }
+}
@@ -44,14 +44,30 @@ pub trait Grid {
     fn get_cell<'a>(&'a self, coord: &Coord) -> Cell<'a>;
     fn left_click_cell(&mut self, coord: &Coord);
     fn right_click_cell(&mut self, coord: &Coord);
+    fn chord_cell(&mut self, coord: &Coord);
+    fn undo(&mut self);
+    fn chat(&mut self, text: &str);
 }
 
 #[derive(Debug)]
-pub struct LivesLeft(pub u32);
+/// None when the players don't share their lives.
+pub struct LivesLeft(pub Option<u32>);
 
 #[derive(Debug)]
 pub struct MinesLeft(pub i32);
 
+/// The time spent playing, shown as minutes and seconds.
+#[derive(Clone, Copy, Debug)]
+pub struct Time(pub std::time::Duration);
+
+impl std::fmt::Display for Time {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Time(time) = self;
+        let seconds = time.as_secs();
+        write!(fmt, "{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
 pub trait StatusGenerator {
     type Grid<'a>: Grid + 'a;
     type Controller<'a>: Controller + 'a;
@@ -69,10 +85,18 @@ where
     Active(Active<G>),
     NonActive {
         nonactive: NonActive<'a, C>,
-        success: bool,
+        outcome: Outcome,
     },
 }
 
+/// How a game came to an end.
+#[derive(Clone, Copy, Debug)]
+pub enum Outcome {
+    Won,
+    Failed,
+    Disconnected,
+}
+
 pub struct Active<G>
 where
     G: Grid,
@@ -81,6 +105,43 @@ where
     pub grid: G,
     pub lives_left: LivesLeft,
     pub mines_left: MinesLeft,
+    pub time: Time,
+    pub players: Vec<PlayerState>,
+    pub spectators: Vec<String>,
+    /// The teams which race the own team, if any.
+    pub rivals: Vec<TeamProgress>,
+    /// From the oldest to the newest line, or none when there is no one to chat with.
+    pub chat: Option<Vec<ChatLine>>,
+}
+
+/// How far a team got on its minefield.
+#[derive(Debug)]
+pub struct TeamProgress {
+    pub name: String,
+    pub players: Vec<String>,
+    pub uncovered: u32,
+    pub safe: u32,
+    pub lives_left: Option<u32>,
+    pub winner: bool,
+}
+
+/// Something a player said, shown in their colour.
+#[derive(Debug)]
+pub struct ChatLine {
+    pub id: PlayerID,
+    pub name: String,
+    pub text: String,
+}
+
+/// A player taking part in the game, who may have lost their connection.
+#[derive(Debug)]
+pub struct PlayerState {
+    pub id: PlayerID,
+    pub name: String,
+    pub connected: bool,
+    /// Only when every player has lives of their own.
+    pub lives_left: Option<u32>,
+    pub score: i32,
 }
 
 #[derive(Debug)]
@@ -98,16 +159,58 @@ pub trait Controller {
 
 #[derive(Debug)]
 pub struct Statistics {
+    /// From the highest to the lowest score.
+    pub scores: Vec<UserStat>,
     pub marked_correct: Vec<UserStat>,
     pub marked_incorrect: Vec<UserStat>,
     pub exploded: Vec<UserStat>,
+    /// From the most to the fewest uncovered cells.
+    pub uncovered: Vec<UncoverStat>,
+    /// From the most to the fewest effective clicks.
+    pub clicks: Vec<ClickStat>,
+    /// None when the mines were never laid out.
+    pub board: Option<Board>,
+    pub time: Time,
+    /// Every team, when teams competed.
+    pub teams: Vec<TeamProgress>,
 }
 
 #[derive(Debug)]
 pub struct UserStat {
     pub id: PlayerID,
     pub name: String,
-    pub number: u32,
+    pub number: i64,
+}
+
+/// How many clicks of a player changed something, and how many were wasted.
+#[derive(Debug)]
+pub struct ClickStat {
+    pub id: PlayerID,
+    pub name: String,
+    pub effective: u32,
+    pub wasted: u32,
+}
+
+/// How difficult the minefield was, and how well it got played.
+#[derive(Debug)]
+pub struct Board {
+    /// The minimum number of clicks which clear the minefield.
+    pub three_bv: u32,
+    /// None when no time passed.
+    pub three_bv_per_second: Option<f64>,
+    /// The 3BV as a share of all clicks, none without any clicks.
+    pub efficiency: Option<f64>,
+}
+
+/// What a player uncovered.
+#[derive(Debug)]
+pub struct UncoverStat {
+    pub id: PlayerID,
+    pub name: String,
+    pub revealed: u32,
+    pub hints: u32,
+    pub openings: u32,
+    pub biggest_opening: u32,
 }
 
 pub struct GridWithTextures<'a, R>
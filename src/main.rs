@@ -1,18 +1,116 @@
-use game::{local, multiplayer as network, session};
+use game::{layout, local, mask, multiplayer as network, session};
 use sioux_rust_minesweeper_crate::*;
 
+fn convert_transport(transport: args::Transport) -> network::Transport {
+    match transport {
+        args::Transport::Udp => network::Transport::Udp,
+        args::Transport::Tcp => network::Transport::Tcp,
+    }
+}
+
+fn convert_codec(codec: args::Codec) -> network::Codec {
+    match codec {
+        args::Codec::Binary => network::Codec::Binary,
+        args::Codec::Json => network::Codec::Json,
+    }
+}
+
 fn convert_config(c: args::Config) -> session::SessionConfig {
     let args::Config {
         width,
         height,
         lives,
+        lives_policy,
         mines,
+        seed,
+        first_click,
+        no_guess,
+        layout,
+        topology,
+        mask,
+        undo,
     } = c;
-    session::SessionConfig {
-        coords: coordinations::Coordinations::from_width_and_height(width, height),
+    let mut config = session::SessionConfig {
+        coords: coordinations::Coordinations::from_width_and_height(width, height).with_topology(
+            match topology {
+                args::Topology::Rectangular => coordinations::Topology::Rectangular,
+                args::Topology::Hexagonal => coordinations::Topology::Hexagonal,
+                args::Topology::Toroidal => coordinations::Topology::Toroidal,
+            },
+        ),
         mines: game::Mines(mines),
         lives: game::Lives(lives),
+        lives_policy: match lives_policy {
+            args::LivesPolicy::Shared => game::LivesPolicy::Shared,
+            args::LivesPolicy::PerPlayer => game::LivesPolicy::PerPlayer,
+            args::LivesPolicy::Unlimited => game::LivesPolicy::Unlimited,
+        },
+        seed: seed.map(game::Seed),
+        first_click: match first_click {
+            args::FirstClick::Unprotected => game::FirstClick::Unprotected,
+            args::FirstClick::Safe => game::FirstClick::Safe,
+            args::FirstClick::Opening => game::FirstClick::Opening,
+        },
+        generator: if no_guess {
+            game::Generator::NoGuess(Default::default())
+        } else {
+            game::Generator::Random
+        },
+        undo,
+    };
+    let layout = layout.map(|path| {
+        layout::FileLayout::load(&path)
+            .unwrap_or_else(|err| fail(format!("Failed to load {}: {err}", path.display())))
+    });
+    let mask = mask.map(|path| {
+        mask::Mask::load(&path)
+            .unwrap_or_else(|err| fail(format!("Failed to load {}: {err}", path.display())))
+    });
+    if let Some(layout) = &layout {
+        config.coords = layout.coords().with_topology(config.coords.topology());
+        config.mines = layout.mines();
+    }
+    if let Some(mask) = mask {
+        config.coords = mask.coords().with_topology(config.coords.topology());
+    }
+    if let Some(layout) = layout {
+        layout.check(&config.coords).unwrap_or_else(|err| fail(err));
+        config.generator =
+            game::Generator::Custom(std::sync::Arc::new(std::sync::Mutex::new(layout)));
+    }
+    if let coordinations::Topology::Toroidal = config.coords.topology() {
+        if config.coords.columns() < 3 || config.coords.rows() < 3 {
+            fail("A toroidal minefield needs at least 3 columns and 3 rows");
+        }
+    }
+    if !matches!(config.generator, game::Generator::Custom(_)) {
+        let safe = match config.first_click {
+            game::FirstClick::Unprotected => 0,
+            game::FirstClick::Safe => 1,
+            game::FirstClick::Opening => 1 + config.coords.max_neighbors() as usize,
+        };
+        let game::Mines(mines) = config.mines;
+        let room = config.coords.active_size().saturating_sub(safe);
+        if mines as usize > room {
+            fail(format!(
+                "{mines} mines don't fit, the minefield has room for at most {room} of them"
+            ));
+        }
     }
+    config
+}
+
+/// Tells what is wrong with the arguments, and exits without a backtrace.
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{message}");
+    std::process::exit(1)
+}
+
+/// The address at which a master listens for slaves.
+fn listen_at(port: u16) -> std::net::SocketAddr {
+    let ip = std::net::Ipv4Addr::UNSPECIFIED;
+    let ip = std::net::IpAddr::V4(ip);
+    std::net::SocketAddr::new(ip, port)
 }
 
 pub fn main() {
@@ -27,19 +125,56 @@ pub fn main() {
             name,
             slave,
             master,
+            transport,
+            codec,
+            timeout,
+            rejoin,
+            spectate,
         } => {
             let slave = network::slave::Slave(slave);
             let master = network::slave::Master(master);
-            adapter::Main::new(network::slave::Manager::new(name, slave, master)).exec()
-        }
-        args::Modus::Master { name, config, port } => {
-            let master = {
-                let ip = std::net::Ipv4Addr::UNSPECIFIED;
-                let ip = std::net::IpAddr::V4(ip);
-                std::net::SocketAddr::new(ip, port)
+            let transport = convert_transport(transport);
+            let codec = convert_codec(codec);
+            let join_as = match spectate {
+                true => network::slave::JoinAs::Spectator,
+                false => network::slave::JoinAs::Player(rejoin.map(network::RejoinToken)),
             };
+            adapter::Main::new(network::slave::Manager::new(
+                name, slave, master, transport, codec, timeout, join_as,
+            ))
+            .exec()
+        }
+        args::Modus::Master {
+            name,
+            config,
+            port,
+            transport,
+            codec,
+            timeout,
+            teams,
+        } => {
+            let master = listen_at(port);
+            let config = convert_config(config);
+            let transport = convert_transport(transport);
+            let codec = convert_codec(codec);
+            adapter::Main::new(network::master::Manager::new(
+                name, master, transport, codec, timeout, config, teams,
+            ))
+            .exec()
+        }
+        args::Modus::Serve {
+            config,
+            port,
+            transport,
+            codec,
+            timeout,
+            teams,
+        } => {
+            let master = listen_at(port);
             let config = convert_config(config);
-            adapter::Main::new(network::master::Manager::new(name, master, config)).exec()
+            let transport = convert_transport(transport);
+            let codec = convert_codec(codec);
+            network::master::Headless::new(master, transport, codec, timeout, config, teams).serve()
         }
     }
 }